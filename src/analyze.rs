@@ -0,0 +1,298 @@
+//! `/dcm analyze`: run `dcm analyze` once and collect the issues it reports.
+//!
+//! The command mirrors the server's view of the worktree: the same executable,
+//! Dart SDK and environment, with `excluded_folders` passed as `--exclude`
+//! globs relative to the worktree root.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use zed_extension_api::Command;
+
+use crate::config::ResolvedSettings;
+use crate::error::DcmError;
+use crate::host::Host;
+use crate::language_server;
+use crate::utils::path_to_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    Error,
+    Warning,
+    Performance,
+    Style,
+    Info,
+    #[serde(other)]
+    None,
+}
+
+impl IssueSeverity {
+    pub fn label(self) -> &'static str {
+        match self {
+            IssueSeverity::Error => "error",
+            IssueSeverity::Warning => "warning",
+            IssueSeverity::Performance => "performance",
+            IssueSeverity::Style => "style",
+            IssueSeverity::Info => "info",
+            IssueSeverity::None => "none",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub rule_id: String,
+    pub severity: IssueSeverity,
+    /// One-based line and column of the start of the issue.
+    pub line: u64,
+    pub column: u64,
+    pub message: String,
+}
+
+/// The issues of one analyzed file, ordered by position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileIssues {
+    /// Relative to the worktree root when the file is inside it.
+    pub path: String,
+    pub issues: Vec<Issue>,
+}
+
+/// Shape of `dcm analyze --reporter=json` output; fields the extension does
+/// not show are ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Report {
+    records: Vec<Record>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Record {
+    path: String,
+    issues: Vec<RawIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawIssue {
+    rule_id: String,
+    severity: IssueSeverity,
+    message: String,
+    code_span: CodeSpan,
+}
+
+#[derive(Debug, Deserialize)]
+struct CodeSpan {
+    start: Location,
+}
+
+#[derive(Debug, Deserialize)]
+struct Location {
+    line: u64,
+    column: u64,
+}
+
+/// Analyzes `paths`, or the whole worktree when none are given, returning
+/// the files with issues ordered by path.
+pub fn run(
+    host: &dyn Host,
+    settings: &ResolvedSettings,
+    paths: &[PathBuf],
+) -> Result<Vec<FileIssues>, DcmError> {
+    let command = build_command(settings, paths)?;
+    let output = host.run(&command)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // `dcm analyze` exits non-zero when it finds issues, so the status alone
+    // does not mean it failed.
+    parse_report(&stdout, &settings.root_path).map_err(|err| {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let status = output
+            .status
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let detail = match stderr.trim() {
+            "" => format!("{err} (exit status {status})"),
+            stderr => format!("{err} (exit status {status}): {stderr}"),
+        };
+        DcmError::cli_output("dcm analyze --reporter=json", detail)
+    })
+}
+
+pub fn build_command(settings: &ResolvedSettings, paths: &[PathBuf]) -> Result<Command, DcmError> {
+    let mut args = vec!["analyze".to_string(), "--reporter=json".to_string()];
+    if let Some(sdk_path) = &settings.sdk_path {
+        args.push(format!("--sdk-path={}", path_to_string(sdk_path)?));
+    }
+    let excluded: Vec<String> = settings
+        .excluded_folders
+        .iter()
+        .filter_map(|folder| folder.strip_prefix(&settings.root_path).ok())
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .map(|folder| format!("{}/**", folder.to_string_lossy()))
+        .collect();
+    if !excluded.is_empty() {
+        args.push(format!("--exclude={{{}}}", excluded.join(",")));
+    }
+    if paths.is_empty() {
+        args.push(path_to_string(&settings.root_path)?);
+    } else {
+        for path in paths {
+            args.push(path_to_string(path)?);
+        }
+    }
+
+    Ok(Command::new(path_to_string(&settings.executable_path)?)
+        .args(args)
+        .envs(language_server::build_environment(settings)?))
+}
+
+/// Parses the JSON report, making file paths relative to `root`.
+pub fn parse_report(stdout: &str, root: &Path) -> Result<Vec<FileIssues>, String> {
+    // Warnings such as update notices may precede the report.
+    let start = stdout
+        .find('{')
+        .ok_or_else(|| "no JSON report in the output".to_string())?;
+    let report: Report = serde_json::from_str(&stdout[start..])
+        .map_err(|err| format!("invalid JSON report: {err}"))?;
+
+    let mut files: Vec<FileIssues> = report
+        .records
+        .into_iter()
+        .filter(|record| !record.issues.is_empty())
+        .map(|record| {
+            let path = Path::new(&record.path);
+            let path = path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned();
+            let mut issues: Vec<Issue> = record
+                .issues
+                .into_iter()
+                .map(|issue| Issue {
+                    rule_id: issue.rule_id,
+                    severity: issue.severity,
+                    line: issue.code_span.start.line,
+                    column: issue.code_span.start.column,
+                    message: issue.message,
+                })
+                .collect();
+            issues.sort_by_key(|issue| (issue.line, issue.column));
+            FileIssues { path, issues }
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::LayeredSettings;
+
+    fn settings() -> ResolvedSettings {
+        ResolvedSettings {
+            layered: LayeredSettings::default(),
+            executable_path: PathBuf::from("/opt/dcm/bin/dcm"),
+            sdk_path: Some(PathBuf::from("/opt/dart-sdk")),
+            root_path: PathBuf::from("/work/app"),
+            excluded_folders: vec![
+                PathBuf::from("/work/app/build"),
+                PathBuf::from("/work/app/lib/gen"),
+                PathBuf::from("/elsewhere"),
+            ],
+            log_file_path: None,
+            log_directory: None,
+            env: vec![("HOME".to_string(), "/home/dev".to_string())],
+        }
+    }
+
+    #[test]
+    fn command_uses_sdk_exclusions_and_paths() {
+        let command = build_command(&settings(), &[PathBuf::from("/work/app/lib")]).unwrap();
+        assert_eq!(command.command, "/opt/dcm/bin/dcm");
+        assert_eq!(
+            command.args,
+            [
+                "analyze",
+                "--reporter=json",
+                "--sdk-path=/opt/dart-sdk",
+                "--exclude={build/**,lib/gen/**}",
+                "/work/app/lib",
+            ]
+        );
+        assert!(command
+            .env
+            .contains(&("DART_SDK".to_string(), "/opt/dart-sdk".to_string())));
+    }
+
+    #[test]
+    fn command_analyzes_the_worktree_by_default() {
+        let command = build_command(&settings(), &[]).unwrap();
+        assert_eq!(command.args.last().map(String::as_str), Some("/work/app"));
+    }
+
+    #[test]
+    fn parses_issues_per_file() {
+        let stdout = r#"A new version of DCM is available.
+{
+  "formatVersion": 9,
+  "records": [
+    {
+      "path": "/work/app/lib/main.dart",
+      "issues": [
+        {
+          "ruleId": "prefer-trailing-comma",
+          "severity": "style",
+          "message": "Prefer trailing comma.",
+          "codeSpan": { "start": { "offset": 90, "line": 7, "column": 3 } }
+        },
+        {
+          "ruleId": "avoid-dynamic",
+          "severity": "warning",
+          "message": "Avoid using dynamic type.",
+          "codeSpan": { "start": { "offset": 12, "line": 2, "column": 10 } }
+        }
+      ]
+    },
+    { "path": "/work/app/lib/clean.dart", "issues": [] },
+    {
+      "path": "/work/app/lib/a.dart",
+      "issues": [
+        {
+          "ruleId": "no-empty-block",
+          "severity": "critical",
+          "message": "Block is empty.",
+          "codeSpan": { "start": { "line": 1, "column": 1 } }
+        }
+      ]
+    }
+  ]
+}"#;
+
+        let files = parse_report(stdout, Path::new("/work/app")).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "lib/a.dart");
+        assert_eq!(files[0].issues[0].severity, IssueSeverity::None);
+        assert_eq!(files[1].path, "lib/main.dart");
+        assert_eq!(
+            files[1].issues[0],
+            Issue {
+                rule_id: "avoid-dynamic".to_string(),
+                severity: IssueSeverity::Warning,
+                line: 2,
+                column: 10,
+                message: "Avoid using dynamic type.".to_string(),
+            }
+        );
+        assert_eq!(files[1].issues[1].rule_id, "prefer-trailing-comma");
+    }
+
+    #[test]
+    fn rejects_output_without_a_report() {
+        assert!(parse_report("Error: no license\n", Path::new("/work/app")).is_err());
+        assert!(parse_report("{ \"records\": 3 }", Path::new("/work/app")).is_err());
+    }
+}
@@ -1,45 +1,230 @@
-use std::fs;
-use std::path::PathBuf;
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
 
 use serde_json::{Map, Value};
 use zed_extension_api::{
     self as zed, SlashCommandArgumentCompletion, SlashCommandOutput, SlashCommandOutputSection,
-    Worktree,
 };
 
-use crate::config::ResolvedSettings;
-use crate::utils::path_to_string;
+use crate::analyze;
+use crate::config::{
+    self, LayeredSettings, SettingSource, PROFILES_KEY, PROFILE_KEY, PROJECT_SETTINGS_FILE,
+    TEAM_SETTINGS_FILE,
+};
+use crate::debug_log;
+use crate::doctor::{self, Diagnosis};
+use crate::error::DcmError;
+use crate::history;
+use crate::host::{FileSystem, Host};
+use crate::jsonc;
+use crate::language_server;
+use crate::log_sessions::{self, Retention};
+use crate::lsp_log;
+use crate::probe::{self, ProbeOutcome};
+use crate::schema::{self, SettingKind, SettingSpec};
+use crate::session::SessionState;
+use crate::trust;
+use crate::utils::{
+    closest_match, env_value, format_age, is_secret_name, path_to_string, read_tail, redact_arg,
+    resolve_path, scrub_text, tail_lines, unix_timestamp,
+};
+use crate::vscode::{self, VSCODE_SETTINGS_FILE};
 
 pub const DCM_SLASH_COMMAND: &str = "dcm";
 
 const OPEN_RULES_URL: &str = "https://dcm.dev/docs/rules/";
 const OPEN_METRICS_URL: &str = "https://dcm.dev/docs/metrics/";
 const FEEDBACK_URL: &str = "https://discord.gg/Vzjprgk4sb";
+const SCHEMA_FILE_NAME: &str = "dcm-settings.schema.json";
+const SUPPORT_BUNDLE_FILE_NAME: &str = "dcm-support-bundle.md";
+/// Log lines, and at most this many bytes, copied into the support bundle.
+const SUPPORT_LOG_LINES: usize = 200;
+const SUPPORT_LOG_BYTES: u64 = 64 * 1024;
+/// Lines `dcm log tail` shows by default, and the most `tail` or `show` return.
+const LOG_DEFAULT_LINES: usize = 50;
+const LOG_MAX_LINES: usize = 500;
+/// Bytes read from the end of the log file, and the most returned to the assistant.
+const LOG_READ_BYTES: u64 = 1024 * 1024;
+const LOG_OUTPUT_BYTES: usize = 32 * 1024;
+/// Bytes read from the end of the log file for `dcm log summary`.
+const LOG_SUMMARY_BYTES: u64 = 16 * 1024 * 1024;
+/// Lines, and at most this many bytes, of the extension debug log shown by `config` and `doctor`.
+const DEBUG_LOG_LINES: usize = 60;
+const DEBUG_LOG_BYTES: u64 = 32 * 1024;
+/// Issues listed by `dcm analyze`; the rest are only counted.
+const ANALYZE_MAX_ISSUES: usize = 500;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum ToggleTarget {
-    Baseline,
-    UnusedCode,
-    UnusedFiles,
-    NewVersion,
-}
-
-pub fn complete(args: &[String]) -> Vec<SlashCommandArgumentCompletion> {
+/// Completes `/dcm` arguments. Zed gives completion no worktree, so paths
+/// are completed below the worktree `root` seen most recently, through `fs`.
+pub fn complete(
+    args: &[String],
+    root: Option<&Path>,
+    fs: &dyn FileSystem,
+) -> Vec<SlashCommandArgumentCompletion> {
     match args.len() {
         0 => top_level_completions(""),
         1 => top_level_completions(&args[0]),
         _ => match args[0].as_str() {
             "open" => subcommand_completions(&["rules", "metrics", "feedback", "logs"], args),
-            "toggle" => subcommand_completions(
-                &["baseline", "unused-code", "unused-files", "new-version"],
+            "toggle" => subcommand_completions(&toggle_names(), args),
+            "set" => set_completions(args),
+            "get" | "unset" if args.len() == 2 => subcommand_completions(&setting_key_list(), args),
+            "log" => subcommand_completions(
+                &[
+                    "capture", "clear", "tail", "show", "summary", "list", "prune",
+                ],
                 args,
             ),
-            "log" => subcommand_completions(&["capture", "clear"], args),
+            "import" | "export" => subcommand_completions(&["vscode"], args),
+            "trust" => subcommand_completions(&["approve", "revoke"], args),
+            "exclude" => exclude_completions(args, root, fs),
+            "analyze" => root
+                .map(|root| path_completions(fs, root, args, true))
+                .unwrap_or_default(),
+            "profile" if args.len() == 2 => {
+                let mut options = vec!["list", "none"];
+                let names = root.map(|root| profile_names(fs, root)).unwrap_or_default();
+                options.extend(names.iter().map(String::as_str));
+                subcommand_completions(&options, args)
+            }
             _ => Vec::new(),
         },
     }
 }
 
+fn set_completions(args: &[String]) -> Vec<SlashCommandArgumentCompletion> {
+    if args.len() == 2 {
+        return subcommand_completions(&setting_key_list(), args)
+            .into_iter()
+            .map(|mut completion| {
+                completion.new_text.push(' ');
+                completion.run_command = false;
+                completion
+            })
+            .collect();
+    }
+
+    let Some(spec) = schema::spec(&args[1]) else {
+        return Vec::new();
+    };
+    match spec.kind {
+        SettingKind::Bool if args.len() == 3 => subcommand_completions(&["true", "false"], args),
+        SettingKind::Path | SettingKind::PathList if args.len() == 3 => {
+            subcommand_completions(&["./", "~/", "$HOME/"], args)
+                .into_iter()
+                .map(|mut completion| {
+                    completion.run_command = false;
+                    completion
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn exclude_completions(
+    args: &[String],
+    root: Option<&Path>,
+    fs: &dyn FileSystem,
+) -> Vec<SlashCommandArgumentCompletion> {
+    if args.len() == 2 {
+        return subcommand_completions(&["add", "remove", "list"], args)
+            .into_iter()
+            .map(|mut completion| {
+                if completion.label != "list" {
+                    completion.new_text.push(' ');
+                    completion.run_command = false;
+                }
+                completion
+            })
+            .collect();
+    }
+
+    let Some(root) = root else {
+        return Vec::new();
+    };
+    match args[1].as_str() {
+        "add" => path_completions(fs, root, args, false),
+        "remove" => {
+            let excluded = read_dcm_block_for_completion(fs, &root.join(PROJECT_SETTINGS_FILE))
+                .and_then(|block| block.get("excluded_folders").cloned())
+                .and_then(|value| value.as_array().cloned())
+                .unwrap_or_default();
+            let entries: Vec<&str> = excluded.iter().filter_map(Value::as_str).collect();
+            subcommand_completions(&entries, args)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Completes folder names below `root` for the last argument, and Dart
+/// files too when `dart_files` is set.
+fn path_completions(
+    fs: &dyn FileSystem,
+    root: &Path,
+    args: &[String],
+    dart_files: bool,
+) -> Vec<SlashCommandArgumentCompletion> {
+    const MAX_COMPLETIONS: usize = 50;
+
+    let partial = args.last().map(String::as_str).unwrap_or("");
+    let (parent, prefix) = match partial.rfind('/') {
+        Some(index) => partial.split_at(index + 1),
+        None => ("", partial),
+    };
+
+    let mut paths: Vec<String> = fs
+        .read_dir(&root.join(parent))
+        .into_iter()
+        .filter_map(|entry| {
+            let name = entry.file_name()?.to_str()?.to_string();
+            if fs.is_dir(&entry) {
+                Some(format!("{name}/"))
+            } else if dart_files && name.ends_with(".dart") {
+                Some(name)
+            } else {
+                None
+            }
+        })
+        .filter(|name| name.starts_with(prefix))
+        .filter(|name| prefix.starts_with('.') || !name.starts_with('.'))
+        .collect();
+    paths.sort();
+    paths.truncate(MAX_COMPLETIONS);
+
+    let leading = args[..args.len() - 1].join(" ");
+    paths
+        .into_iter()
+        .map(|name| {
+            let path = format!("{parent}{name}");
+            SlashCommandArgumentCompletion {
+                new_text: format!("{leading} {path}"),
+                label: path,
+                run_command: false,
+            }
+        })
+        .collect()
+}
+
+/// Profile names defined in the project and team settings files below `root`.
+fn profile_names(fs: &dyn FileSystem, root: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    for file in [PROJECT_SETTINGS_FILE, TEAM_SETTINGS_FILE] {
+        let profiles = read_dcm_block_for_completion(fs, &root.join(file))
+            .and_then(|mut block| block.remove(PROFILES_KEY));
+        if let Some(Value::Object(profiles)) = profiles {
+            names.extend(profiles.keys().cloned());
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn setting_key_list() -> Vec<&'static str> {
+    schema::SETTINGS.iter().map(|spec| spec.key).collect()
+}
+
 fn top_level_completions(input: &str) -> Vec<SlashCommandArgumentCompletion> {
     let options = [
         ("help", "help", false),
@@ -47,6 +232,23 @@ fn top_level_completions(input: &str) -> Vec<SlashCommandArgumentCompletion> {
         ("toggle", "toggle ", false),
         ("restart", "restart", true),
         ("log", "log ", false),
+        ("set", "set ", false),
+        ("get", "get", true),
+        ("unset", "unset ", false),
+        ("exclude", "exclude ", false),
+        ("profile", "profile ", false),
+        ("history", "history", true),
+        ("import", "import ", false),
+        ("export", "export ", false),
+        ("undo", "undo", true),
+        ("trust", "trust", true),
+        ("doctor", "doctor", true),
+        ("probe", "probe", true),
+        ("analyze", "analyze", true),
+        ("support-bundle", "support-bundle", true),
+        ("config", "config", true),
+        ("validate", "validate", true),
+        ("schema", "schema", true),
     ];
 
     options
@@ -79,9 +281,13 @@ fn subcommand_completions(
         .collect()
 }
 
-pub fn run(args: Vec<String>, worktree: Option<&Worktree>) -> zed::Result<SlashCommandOutput> {
-    let Some(worktree) = worktree else {
-        return Err("DCM commands require an active worktree".to_string());
+pub fn run(
+    args: Vec<String>,
+    host: Option<&dyn Host>,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let Some(host) = host else {
+        return Err(DcmError::usage("DCM commands require an active worktree"));
     };
 
     if args.is_empty() {
@@ -90,20 +296,51 @@ pub fn run(args: Vec<String>, worktree: Option<&Worktree>) -> zed::Result<SlashC
 
     match args[0].as_str() {
         "help" => Ok(help_output()),
-        "open" => run_open(&args[1..]),
-        "toggle" => run_toggle(&args[1..], worktree),
-        "log" => run_log(&args[1..], worktree),
-        "restart" => Ok(info_output(
-            "Restart DCM",
-            "Use `Zed: Restart Language Server` from the command palette to restart the DCM server.",
-        )),
-        other => Err(format!("Unknown DCM subcommand `{other}`. Run `dcm help` for options.")),
+        "open" => run_open(&args[1..], host, session),
+        "toggle" => run_toggle(&args[1..], host, session),
+        "log" => run_log(&args[1..], host, session),
+        "set" => run_set(&args[1..], host, session),
+        "get" => run_get(&args[1..], host),
+        "unset" => run_unset(&args[1..], host, session),
+        "exclude" => run_exclude(&args[1..], host, session),
+        "profile" => run_profile(&args[1..], host, session),
+        "history" => run_history(host),
+        "undo" => run_undo(&args[1..], host, session),
+        "import" => run_import(&args[1..], host, session),
+        "export" => run_export(&args[1..], host),
+        "trust" => run_trust(&args[1..], host, session),
+        "doctor" => run_doctor(host, session),
+        "probe" => run_probe(host, session),
+        "analyze" => run_analyze(&args[1..], host, session),
+        "support-bundle" => run_support_bundle(host, session),
+        "config" => run_config(host, session),
+        "validate" => run_validate(host),
+        "schema" => run_schema(host),
+        "restart" => {
+            let settings = LayeredSettings::load(host)?;
+            Ok(info_output(
+                "Restart DCM",
+                &format!(
+                    "Use `Zed: Restart Language Server` from the command palette to restart the DCM server. {}",
+                    session.restart_summary(host, &settings)
+                ),
+            ))
+        }
+        other => Err(DcmError::usage(format!(
+            "Unknown DCM subcommand `{other}`. Run `dcm help` for options."
+        ))),
     }
 }
 
-fn run_open(args: &[String]) -> zed::Result<SlashCommandOutput> {
+fn run_open(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
     if args.is_empty() {
-        return Err("Specify what to open: rules, metrics, feedback, or logs".to_string());
+        return Err(DcmError::usage(
+            "Specify what to open: rules, metrics, feedback, or logs",
+        ));
     }
 
     let (title, message) = match args[0].as_str() {
@@ -113,225 +350,2085 @@ fn run_open(args: &[String]) -> zed::Result<SlashCommandOutput> {
             "DCM Feedback",
             format!("Join the community: {FEEDBACK_URL}"),
         ),
-        "logs" => (
-            "DCM Logs",
-            "Log file is located under `.zed/dcm.log` when capture is enabled.".to_string(),
-        ),
+        "logs" => ("DCM Logs", describe_log_location(host, session)?),
         other => {
-            return Err(format!(
+            return Err(DcmError::usage(format!(
                 "Unknown open target `{other}`. Use rules, metrics, feedback, or logs."
-            ))
+            )))
         }
     };
 
     Ok(info_output(title, &message))
 }
 
-fn run_toggle(args: &[String], worktree: &Worktree) -> zed::Result<SlashCommandOutput> {
+fn run_toggle(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let names = toggle_names().join(", ");
     if args.is_empty() {
-        return Err(
-            "Specify toggle target: baseline, unused-code, unused-files, or new-version"
-                .to_string(),
-        );
+        return Err(DcmError::usage(format!("Specify toggle target: {names}")));
     }
 
-    let target = match args[0].as_str() {
-        "baseline" => ToggleTarget::Baseline,
-        "unused-code" => ToggleTarget::UnusedCode,
-        "unused-files" => ToggleTarget::UnusedFiles,
-        "new-version" => ToggleTarget::NewVersion,
-        other => {
-            return Err(format!(
-                "Unknown toggle target `{other}`. Use baseline, unused-code, unused-files, or new-version."
-            ))
-        }
+    let Some((spec, toggle)) = schema::SETTINGS.iter().find_map(|spec| {
+        spec.toggle
+            .as_ref()
+            .filter(|toggle| toggle.name == args[0])
+            .map(|toggle| (spec, toggle))
+    }) else {
+        return Err(DcmError::usage(format!(
+            "Unknown toggle target `{}`. Use {names}.",
+            args[0]
+        )));
     };
 
-    let settings = ResolvedSettings::from_worktree(worktree)?;
-    let mut root_obj = read_settings_map(worktree)?;
-    let mut dcm_map = root_obj
-        .remove("dcm")
-        .and_then(|value| value.as_object().cloned())
-        .unwrap_or_else(Map::new);
-
-    let (title, message) = match target {
-        ToggleTarget::Baseline => {
-            let new_value = !settings.user.disable_baseline;
-            dcm_map.insert("disable_baseline".to_string(), Value::Bool(new_value));
-            (
-                "Baseline Toggle",
-                if new_value {
-                    "Baseline filtering disabled. Restart the DCM server for changes to take effect."
-                } else {
-                    "Baseline filtering enabled. Restart the DCM server for changes to take effect."
-                },
-            )
-        }
-        ToggleTarget::UnusedCode => {
-            let new_value = !settings.user.show_unused_code;
-            dcm_map.insert("show_unused_code".to_string(), Value::Bool(new_value));
-            (
-                "Unused Code Toggle",
-                if new_value {
-                    "Unused code issues will be reported after restarting the DCM server."
-                } else {
-                    "Unused code issues suppressed. Restart the DCM server for changes to take effect."
-                },
-            )
-        }
-        ToggleTarget::UnusedFiles => {
-            let new_value = !settings.user.show_unused_files;
-            dcm_map.insert("show_unused_files".to_string(), Value::Bool(new_value));
-            (
-                "Unused Files Toggle",
-                if new_value {
-                    "Unused file analysis enabled. Restart the DCM server for changes to take effect."
-                } else {
-                    "Unused file analysis disabled. Restart the DCM server for changes to take effect."
-                },
+    let settings = LayeredSettings::load(host)?;
+    let new_value = !settings.value(spec.key).as_bool().unwrap_or(false);
+    update_setting(host, &settings, spec, Some(Value::Bool(new_value)))?;
+
+    let message = if new_value {
+        toggle.enabled
+    } else {
+        toggle.disabled
+    };
+    let updated = LayeredSettings::load(host)?;
+    Ok(info_output(
+        &format!("{} Toggle", toggle.title),
+        &with_setting_notes(message, spec, &updated, session, host),
+    ))
+}
+
+fn run_set(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let Some((key, raw_value)) = args.split_first() else {
+        return Err(DcmError::usage(format!(
+            "Usage: dcm set <key> <value>. Keys: {}",
+            setting_keys()
+        )));
+    };
+    let spec = setting_spec(key)?;
+    if raw_value.is_empty() {
+        return Err(DcmError::usage(format!(
+            "Specify a value for `{}`: {}",
+            spec.key,
+            spec.kind.describe()
+        )));
+    }
+
+    let value = spec
+        .kind
+        .parse_args(raw_value)
+        .map_err(|err| DcmError::usage(format!("Invalid value for `{}`: {err}", spec.key)))?;
+    validate_setting_value(host, spec, &value)?;
+
+    let settings = LayeredSettings::load(host)?;
+    update_setting(host, &settings, spec, Some(value.clone()))?;
+
+    let message = format!("`{}` set to {value} in {PROJECT_SETTINGS_FILE}.", spec.key);
+    let updated = LayeredSettings::load(host)?;
+    Ok(info_output(
+        "DCM Setting Updated",
+        &with_setting_notes(&message, spec, &updated, session, host),
+    ))
+}
+
+fn run_get(args: &[String], host: &dyn Host) -> Result<SlashCommandOutput, DcmError> {
+    let settings = LayeredSettings::load(host)?;
+    let specs: Vec<&SettingSpec> = match args.first() {
+        Some(key) => vec![setting_spec(key)?],
+        None => schema::SETTINGS.iter().collect(),
+    };
+
+    let lines: Vec<String> = specs
+        .iter()
+        .map(|spec| {
+            format!(
+                "{}: {} ({})",
+                spec.key,
+                settings.value(spec.key),
+                settings.source_of(spec.key).label()
             )
+        })
+        .collect();
+    Ok(info_output("DCM Settings", &lines.join("\n")))
+}
+
+fn run_unset(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let Some(key) = args.first() else {
+        return Err(DcmError::usage(format!(
+            "Usage: dcm unset <key>. Keys: {}",
+            setting_keys()
+        )));
+    };
+    let spec = setting_spec(key)?;
+
+    let settings = LayeredSettings::load(host)?;
+    update_setting(host, &settings, spec, None)?;
+
+    let updated = LayeredSettings::load(host)?;
+    let message = format!(
+        "`{}` removed from {PROJECT_SETTINGS_FILE}. Effective value is now {} ({}).",
+        spec.key,
+        updated.value(spec.key),
+        updated.source_of(spec.key).label()
+    );
+    Ok(info_output(
+        "DCM Setting Removed",
+        &with_setting_notes(&message, spec, &updated, session, host),
+    ))
+}
+
+fn setting_spec(key: &str) -> Result<&'static SettingSpec, DcmError> {
+    schema::spec(key)
+        .or_else(|| schema::SETTINGS.iter().find(|spec| spec.camel_key == key))
+        .ok_or_else(|| {
+            let hint = closest_match(key, schema::SETTINGS.iter().map(|spec| spec.key))
+                .map(|key| format!(" Did you mean `{key}`?"))
+                .unwrap_or_default();
+            DcmError::usage(format!("Unknown DCM setting `{key}`.{hint}"))
+        })
+}
+
+fn setting_keys() -> String {
+    schema::SETTINGS
+        .iter()
+        .map(|spec| spec.key)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Rejects values that would make the server fail to start.
+fn validate_setting_value(
+    host: &dyn Host,
+    spec: &SettingSpec,
+    value: &Value,
+) -> Result<(), DcmError> {
+    let Some(raw) = value.as_str() else {
+        return Ok(());
+    };
+    let root = host.root_path();
+    let path = resolve_path(raw, &host.shell_env(), &root)?;
+
+    match spec.key {
+        "executable_path" if !host.is_file(&path) => Err(DcmError::ExecutableMissing(path)),
+        "dart_sdk_path" if !host.is_dir(&path) => Err(DcmError::Sdk(format!(
+            "Dart SDK directory not found at {}",
+            path_to_string(&path)?
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Writes `value` for `spec` into the project settings, or removes it with `None`.
+fn update_setting(
+    host: &dyn Host,
+    settings: &LayeredSettings,
+    spec: &SettingSpec,
+    value: Option<Value>,
+) -> Result<(), DcmError> {
+    if settings.is_locked(spec.key) {
+        return Err(DcmError::settings(format!(
+            "`dcm.{}` is locked by the team settings in {TEAM_SETTINGS_FILE}. Change it there or ask the file's owners to unlock it.",
+            spec.key
+        )));
+    }
+
+    let original = read_settings_map(host)?;
+    let mut dcm_map = original.clone();
+    let change = match value {
+        Some(value) => {
+            let change = format!("set {} {value}", spec.key);
+            dcm_map.remove(spec.camel_key);
+            dcm_map.insert(spec.key.to_string(), value);
+            change
         }
-        ToggleTarget::NewVersion => {
-            let new_value = !settings.user.show_new_version;
-            dcm_map.insert("show_new_version".to_string(), Value::Bool(new_value));
-            (
-                "Version Notification Toggle",
-                if new_value {
-                    "New version notifications enabled."
-                } else {
-                    "New version notifications disabled."
-                },
-            )
+        None => {
+            dcm_map.remove(spec.key);
+            dcm_map.remove(spec.camel_key);
+            format!("unset {}", spec.key)
         }
     };
+    write_settings_map(host, &change, &original, dcm_map)
+}
+
+/// Appends restart and override hints, plus any settings warnings, to `message`.
+fn with_setting_notes(
+    message: &str,
+    spec: &SettingSpec,
+    settings: &LayeredSettings,
+    session: &SessionState,
+    host: &dyn Host,
+) -> String {
+    let mut message = format!("{message} {}", session.restart_summary(host, settings));
+    match (settings.source_of(spec.key), &settings.profile) {
+        (SettingSource::Env, _) => message.push_str(&format!(
+            " Note: an environment variable overrides `{}`.",
+            spec.key
+        )),
+        (SettingSource::Profile, Some(profile)) => message.push_str(&format!(
+            " Note: the active profile `{profile}` overrides `{}`.",
+            spec.key
+        )),
+        _ => {}
+    }
+
+    let warnings: Vec<String> = settings.warnings().map(|w| w.render()).collect();
+    if !warnings.is_empty() {
+        message.push_str(&format!(
+            "\n\nSettings warnings (run `dcm validate` for details):\n{}",
+            warnings.join("\n")
+        ));
+    }
+    message
+}
 
-    write_settings_map(worktree, root_obj, dcm_map)?;
-    Ok(info_output(title, message))
+fn toggle_names() -> Vec<&'static str> {
+    schema::SETTINGS
+        .iter()
+        .filter_map(|spec| spec.toggle.as_ref().map(|toggle| toggle.name))
+        .collect()
 }
 
-fn run_log(args: &[String], worktree: &Worktree) -> zed::Result<SlashCommandOutput> {
+fn run_log(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
     if args.is_empty() {
-        return Err("Specify log command: capture or clear".to_string());
+        return Err(DcmError::usage(
+            "Specify log command: capture, clear, tail, show, summary, list, or prune",
+        ));
+    }
+    match args[0].as_str() {
+        "tail" => return run_log_tail(&args[1..], host, session),
+        "show" => return run_log_show(&args[1..], host, session),
+        "summary" => return run_log_summary(host, session),
+        "list" => return run_log_list(host, session),
+        "prune" => return run_log_prune(&args[1..], host, session),
+        _ => {}
     }
 
-    let mut root_obj = read_settings_map(worktree)?;
-    let mut dcm_map = root_obj
-        .remove("dcm")
-        .and_then(|value| value.as_object().cloned())
-        .unwrap_or_else(Map::new);
+    let original = read_settings_map(host)?;
+    let mut dcm_map = original.clone();
 
     match args[0].as_str() {
         "capture" => {
-            let log_path = default_log_path(worktree);
+            let log_dir = default_log_dir(host);
             dcm_map.insert(
-                "log_file_path".to_string(),
-                Value::String(path_to_string(&log_path)?),
+                "log_directory".to_string(),
+                Value::String(path_to_string(&log_dir)?),
             );
-            write_settings_map(worktree, root_obj, dcm_map)?;
-            Ok(info_output(
-                "Log Capture Enabled",
-                &format!(
-                    "Server communication will be captured to {}. Restart DCM to begin logging.",
-                    path_to_string(&log_path)?
-                ),
-            ))
+            write_settings_map(host, "log capture", &original, dcm_map)?;
+            let updated = LayeredSettings::load(host)?;
+            let mut message = format!(
+                "Each server session will be captured to a new file in {}, keeping the newest {} files and at most {} MB. {}",
+                path_to_string(&log_dir)?,
+                updated.user.log_max_files,
+                updated.user.log_max_size_mb,
+                session.restart_summary(host, &updated)
+            );
+            if env_value(&host.shell_env(), "DCM_LOG_FILE_PATH").is_some() {
+                message.push_str(
+                    " Note: `DCM_LOG_FILE_PATH` is set, so the server keeps writing to that file instead of per-session logs.",
+                );
+            }
+            Ok(info_output("Log Capture Enabled", &message))
         }
         "clear" => {
             dcm_map.remove("log_file_path");
-            write_settings_map(worktree, root_obj, dcm_map)?;
+            dcm_map.remove("log_directory");
+            write_settings_map(host, "log clear", &original, dcm_map)?;
             Ok(info_output(
                 "Log Capture Disabled",
                 "DCM log capture disabled. Delete existing log files manually if desired.",
             ))
         }
-        other => Err(format!(
-            "Unknown log command `{other}`. Use capture or clear."
-        )),
+        other => Err(DcmError::usage(format!(
+            "Unknown log command `{other}`. Use capture, clear, tail, show, summary, list, or prune."
+        ))),
     }
 }
 
-fn help_output() -> SlashCommandOutput {
-    let text = r#"DCM commands:
-- dcm help
-- dcm open [rules|metrics|feedback|logs]
-- dcm toggle [baseline|unused-code|unused-files|new-version]
-- dcm log [capture|clear]
-- dcm restart"#;
+fn run_log_tail(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let count = match args.first() {
+        Some(raw) => raw
+            .parse::<usize>()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or_else(|| DcmError::usage(format!("Expected a number of lines, found `{raw}`")))?,
+        None => LOG_DEFAULT_LINES,
+    }
+    .min(LOG_MAX_LINES);
 
-    SlashCommandOutput {
-        text: text.to_string(),
-        sections: Vec::new(),
+    let (path, text, truncated) = read_captured_log(host, session, LOG_READ_BYTES)?;
+    let lines: Vec<&str> = text.lines().collect();
+    let shown = &lines[lines.len().saturating_sub(count)..];
+    log_output(
+        host,
+        &path,
+        truncated,
+        &format!("Last {} lines", shown.len()),
+        shown,
+    )
+}
+
+fn run_log_show(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let pattern = args.join(" ");
+    if pattern.trim().is_empty() {
+        return Err(DcmError::usage("Usage: dcm log show <pattern>"));
     }
+
+    let (path, text, truncated) = read_captured_log(host, session, LOG_READ_BYTES)?;
+    let needle = pattern.to_lowercase();
+    let matches: Vec<&str> = text
+        .lines()
+        .filter(|line| line.to_lowercase().contains(&needle))
+        .collect();
+    let shown = &matches[matches.len().saturating_sub(LOG_MAX_LINES)..];
+    let label = if shown.len() < matches.len() {
+        format!(
+            "Last {} of {} lines matching `{pattern}`",
+            shown.len(),
+            matches.len()
+        )
+    } else {
+        format!("{} lines matching `{pattern}`", matches.len())
+    };
+    log_output(host, &path, truncated, &label, shown)
 }
 
-fn info_output(title: &str, message: &str) -> SlashCommandOutput {
-    SlashCommandOutput {
-        text: format!("{title}: {message}"),
-        sections: vec![SlashCommandOutputSection {
-            range: zed::Range {
-                start: 0,
-                end: title.len() as u32,
-            },
-            label: title.to_string(),
-        }],
+fn run_log_summary(
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let (path, text, truncated) = read_captured_log(host, session, LOG_SUMMARY_BYTES)?;
+    let summary = lsp_log::summarize(&text);
+    let mut output = SectionedOutput::default();
+
+    let mut overview = vec![
+        format!("File: {}", path.display()),
+        format!("JSON-RPC messages: {}", summary.messages),
+    ];
+    if let Some(span) = summary.span_millis {
+        overview.push(format!("Time covered: {:.1}s", span as f64 / 1000.0));
+    }
+    if truncated {
+        overview.push(format!(
+            "Only the last {} MB of the file were read.",
+            LOG_SUMMARY_BYTES / (1024 * 1024)
+        ));
+    }
+    if summary.messages == 0 {
+        overview.push(
+            "No JSON-RPC messages were found. The server may not have started, or the log uses an unexpected format."
+                .to_string(),
+        );
+    }
+    output.push_section("DCM Log Summary", &overview.join("\n"));
+
+    if !summary.slowest.is_empty() {
+        let lines: Vec<String> = summary
+            .slowest
+            .iter()
+            .map(|request| {
+                format!(
+                    "- {} ms {} ({})",
+                    request.millis, request.method, request.id
+                )
+            })
+            .collect();
+        output.push_section("Slowest Requests", &lines.join("\n"));
+
+        let mut methods: Vec<_> = summary.by_method.iter().collect();
+        methods.sort_by_key(|(_, stats)| Reverse(stats.max_millis));
+        let lines: Vec<String> = methods
+            .iter()
+            .map(|(method, stats)| {
+                format!(
+                    "- {method}: {} requests, avg {} ms, max {} ms",
+                    stats.count,
+                    stats.total_millis / stats.count as u64,
+                    stats.max_millis
+                )
+            })
+            .collect();
+        output.push_section("Requests by Method", &lines.join("\n"));
+    }
+
+    if !summary.unanswered.is_empty() {
+        output.push_section("Unanswered Requests", &summary.unanswered.join("\n"));
+    }
+
+    if !summary.errors.is_empty() {
+        let lines: Vec<String> = summary
+            .errors
+            .iter()
+            .map(|error| {
+                let code = error
+                    .code
+                    .map(|code| format!(" [{code}]"))
+                    .unwrap_or_default();
+                format!("- {}{code}: {}", error.method, error.message)
+            })
+            .collect();
+        output.push_section("Error Responses", &lines.join("\n"));
+    }
+
+    if !summary.exceptions.is_empty() {
+        let lines: Vec<String> = summary
+            .exceptions
+            .iter()
+            .map(|exception| format!("- {exception}"))
+            .collect();
+        output.push_section("Server Exceptions", &lines.join("\n"));
+    }
+
+    if !summary.diagnostics.is_empty() {
+        let lines: Vec<String> = summary
+            .busiest_files()
+            .into_iter()
+            .map(|(uri, stats)| {
+                format!(
+                    "- {uri}: {} publishes, {} diagnostics now",
+                    stats.publishes, stats.latest
+                )
+            })
+            .collect();
+        output.push_section(
+            &format!(
+                "Diagnostics Published ({} files)",
+                summary.diagnostics.len()
+            ),
+            &lines.join("\n"),
+        );
     }
+
+    let mut output = output.finish();
+    output.text = scrub_text(&output.text, &host.shell_env());
+    Ok(output)
 }
 
-fn read_settings_map(worktree: &Worktree) -> zed::Result<Map<String, Value>> {
-    let path = settings_file_path(worktree);
-    if path.exists() {
-        let contents = fs::read_to_string(&path)
-            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
-        if contents.trim().is_empty() {
-            Ok(Map::<String, Value>::new())
-        } else {
-            let value: Value = serde_json::from_str(&contents)
-                .map_err(|err| format!("Invalid JSON in {}: {err}", path.display()))?;
-            if let Some(map) = value.as_object() {
-                Ok(map.clone())
+/// Reads the end of the current log file, returning its path, the text and
+/// whether earlier content was skipped.
+fn read_captured_log(
+    host: &dyn Host,
+    session: &SessionState,
+    max_bytes: u64,
+) -> Result<(PathBuf, String, bool), DcmError> {
+    let path = captured_log_path(host, session)?;
+    let Some((text, truncated)) = read_tail(host, &path, max_bytes) else {
+        return Err(DcmError::filesystem(
+            "read",
+            &path,
+            "there is no log yet. Restart the DCM server so it starts writing one.",
+        ));
+    };
+    Ok((path, text, truncated))
+}
+
+/// The log the server writes to: the current session's file in per-session
+/// mode (or the newest one, before the server starts), otherwise `log_file_path`.
+fn captured_log_path(host: &dyn Host, session: &SessionState) -> Result<PathBuf, DcmError> {
+    let settings = LayeredSettings::load(host)?;
+    let root = host.root_path();
+    let env = host.shell_env();
+
+    if let Some(raw) = settings.user.log_directory.as_deref() {
+        if let Some(path) = session.session_log(host.id()) {
+            return Ok(path);
+        }
+        let dir = resolve_path(raw, &env, &root)?;
+        return log_sessions::list(host, &dir)
+            .into_iter()
+            .next()
+            .map(|log| log.path)
+            .ok_or_else(|| {
+                DcmError::filesystem(
+                    "find session logs in",
+                    &dir,
+                    "there are none yet. Restart the DCM server so it starts writing one.",
+                )
+            });
+    }
+
+    match settings.user.log_file_path.as_deref() {
+        Some(raw) => resolve_path(raw, &env, &root),
+        None => Err(DcmError::usage(
+            "Log capture is disabled. Run `dcm log capture`, then restart the server.",
+        )),
+    }
+}
+
+fn run_log_list(host: &dyn Host, session: &SessionState) -> Result<SlashCommandOutput, DcmError> {
+    let (dir, settings) = session_log_dir(host, session)?;
+    let active = session.session_log(host.id());
+    let logs = log_sessions::list(host, &dir);
+
+    let mut lines: Vec<String> = logs
+        .iter()
+        .map(|log| {
+            let marker = if active.as_ref() == Some(&log.path) {
+                " (current session)"
             } else {
-                Err(format!(
-                    "Settings file {} must contain a JSON object",
-                    path.display()
-                ))
+                ""
+            };
+            format!(
+                "- {}: {} KB, written {}{marker}",
+                log.name(),
+                log.size.div_ceil(1024),
+                format_age(log.modified)
+            )
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push("No session logs yet.".to_string());
+    }
+    let total: u64 = logs.iter().map(|log| log.size).sum();
+    lines.push(String::new());
+    lines.push(format!(
+        "{} files, {:.1} MB in {}. Keeping the newest {} files and at most {} MB.",
+        logs.len(),
+        total as f64 / (1024.0 * 1024.0),
+        dir.display(),
+        settings.layered.user.log_max_files,
+        settings.layered.user.log_max_size_mb
+    ));
+    Ok(info_output("DCM Session Logs", &lines.join("\n")))
+}
+
+fn run_log_prune(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let (dir, settings) = session_log_dir(host, session)?;
+    let retention = match args.first().map(String::as_str) {
+        None => Retention::new(
+            settings.layered.user.log_max_files,
+            settings.layered.user.log_max_size_mb,
+        ),
+        Some("all") => Retention::new(0, 0),
+        Some(other) => {
+            return Err(DcmError::usage(format!(
+                "Unknown prune option `{other}`. Use `dcm log prune` or `dcm log prune all`."
+            )))
+        }
+    };
+
+    let active = session.session_log(host.id());
+    let removed = log_sessions::prune(host, &dir, retention, active.as_deref())?;
+    let freed: u64 = removed.iter().map(|log| log.size).sum();
+    let mut message = format!(
+        "Removed {} session logs ({} KB) from {}.",
+        removed.len(),
+        freed.div_ceil(1024),
+        dir.display()
+    );
+    if active.is_some() {
+        message.push_str(" The current session's log was kept.");
+    }
+    Ok(info_output("DCM Session Logs Pruned", &message))
+}
+
+/// The resolved `log_directory`, or an error explaining how to turn it on.
+///
+/// Fails like a server start would while the directory still needs approval,
+/// so listing and pruning never touch an untrusted location.
+fn session_log_dir(
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<(PathBuf, config::ResolvedSettings), DcmError> {
+    let settings = session.resolve(host)?;
+    let Some(dir) = settings.log_directory.clone() else {
+        return Err(DcmError::usage(
+            "Per-session logs are off. Run `dcm log capture` to turn them on.",
+        ));
+    };
+    trust::ensure_trusted(host, &settings)?;
+    Ok((dir, settings))
+}
+
+fn log_output(
+    host: &dyn Host,
+    path: &Path,
+    truncated: bool,
+    label: &str,
+    lines: &[&str],
+) -> Result<SlashCommandOutput, DcmError> {
+    let mut notes = vec![format!("File: {}", path.display())];
+    if truncated {
+        notes.push(format!(
+            "Only the last {} KB of the file were read.",
+            LOG_READ_BYTES / 1024
+        ));
+    }
+
+    let mut body = lines.join("\n");
+    if body.len() > LOG_OUTPUT_BYTES {
+        let mut cut = body.len() - LOG_OUTPUT_BYTES;
+        while !body.is_char_boundary(cut) {
+            cut += 1;
+        }
+        let start = body[cut..].find('\n').map(|i| cut + i + 1).unwrap_or(cut);
+        body = body[start..].to_string();
+        notes.push(format!(
+            "Earlier lines were left out to stay under {} KB.",
+            LOG_OUTPUT_BYTES / 1024
+        ));
+    }
+    if body.is_empty() {
+        body = "(no lines)".to_string();
+    }
+
+    let mut output = SectionedOutput::default();
+    output.push_section("DCM Log", &notes.join("\n"));
+    output.push_section(label, &scrub_text(&body, &host.shell_env()));
+    Ok(output.finish())
+}
+
+fn run_exclude(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let Some((action, paths)) = args.split_first() else {
+        return Err(DcmError::usage(
+            "Specify exclude command: add, remove, or list",
+        ));
+    };
+
+    let root = host.root_path();
+    let mut settings = LayeredSettings::load(host)?;
+    let spec = setting_spec("excluded_folders")?;
+    // Only the project file is edited, so add and remove work on its own
+    // array rather than the merged value from every layer.
+    let project = read_settings_map(host)?;
+    let mut folders: Vec<String> = project
+        .get(spec.key)
+        .or_else(|| project.get(spec.camel_key))
+        .and_then(Value::as_array)
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let title = match action.as_str() {
+        "list" => "Excluded Folders",
+        "add" | "remove" if paths.is_empty() => {
+            return Err(DcmError::usage(format!(
+                "Specify at least one folder to {action}"
+            )));
+        }
+        "add" => {
+            for raw in paths {
+                let folder = relative_to_root(raw, &root);
+                if !folders
+                    .iter()
+                    .any(|existing| relative_to_root(existing, &root) == folder)
+                {
+                    folders.push(folder);
+                }
             }
+            "Excluded Folders Updated"
+        }
+        "remove" => {
+            for raw in paths {
+                let folder = relative_to_root(raw, &root);
+                let before = folders.len();
+                folders.retain(|existing| relative_to_root(existing, &root) != folder);
+                if folders.len() == before {
+                    return Err(DcmError::usage(format!(
+                        "`{folder}` is not in `excluded_folders` in {PROJECT_SETTINGS_FILE}"
+                    )));
+                }
+            }
+            "Excluded Folders Updated"
+        }
+        other => {
+            return Err(DcmError::usage(format!(
+                "Unknown exclude command `{other}`. Use add, remove, or list."
+            )))
+        }
+    };
+
+    if action != "list" {
+        let value = Value::Array(folders.into_iter().map(Value::String).collect());
+        update_setting(host, &settings, spec, Some(value))?;
+        settings = LayeredSettings::load(host)?;
+    }
+    let folders = &settings.user.excluded_folders;
+
+    let env = host.shell_env();
+    let mut lines = Vec::new();
+    for folder in folders {
+        let exists = host.is_dir(&resolve_path(folder, &env, &root)?);
+        let marker = if exists { "ok" } else { "missing" };
+        lines.push(format!("- {folder} ({marker})"));
+    }
+    if lines.is_empty() {
+        lines.push("No folders are excluded.".to_string());
+    }
+
+    let resolved = config::resolve_excluded_folders(host, &settings.user, &env, &root)?;
+    if !resolved.is_empty() {
+        lines.push(String::new());
+        lines.push("Passed to the server as:".to_string());
+        for path in resolved {
+            lines.push(format!("- {}", path_to_string(&path)?));
         }
+    }
+    lines.push(String::new());
+    lines.push(session.restart_summary(host, &settings));
+
+    Ok(info_output(title, &lines.join("\n")))
+}
+
+/// Normalizes a folder to a worktree-relative path without `./` or a trailing slash.
+fn relative_to_root(raw: &str, root: &Path) -> String {
+    let trimmed = raw.trim();
+    let path = Path::new(trimmed);
+    let relative = path
+        .strip_prefix(root)
+        .map(|rest| rest.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| trimmed.to_string());
+    let relative = relative.trim_start_matches("./").trim_end_matches('/');
+    if relative.is_empty() {
+        ".".to_string()
     } else {
-        Ok(Map::<String, Value>::new())
+        relative.to_string()
     }
 }
 
-fn write_settings_map(
-    worktree: &Worktree,
-    mut root_obj: Map<String, Value>,
-    dcm_map: Map<String, Value>,
-) -> zed::Result<()> {
-    let path = settings_file_path(worktree);
-    root_obj.insert("dcm".to_string(), Value::Object(dcm_map));
-    let final_root = Value::Object(root_obj);
+fn run_profile(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let settings = LayeredSettings::load(host)?;
+    let Some(name) = args.first() else {
+        return Err(DcmError::usage("Specify a profile name, `none`, or `list`"));
+    };
 
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|err| {
-            format!(
-                "Failed to create settings directory {}: {err}",
-                parent.display()
-            )
-        })?;
+    if name == "list" {
+        if settings.profiles.is_empty() {
+            return Ok(info_output(
+                "DCM Profiles",
+                "No profiles defined. Add them under `dcm.profiles` in your settings.",
+            ));
+        }
+
+        let mut output = SectionedOutput::default();
+        for (profile, values) in &settings.profiles {
+            let active = if settings.profile.as_ref() == Some(profile) {
+                " (active)"
+            } else {
+                ""
+            };
+            let body = serde_json::to_string_pretty(values)
+                .map_err(|err| DcmError::settings(format!("Failed to serialize profile: {err}")))?;
+            output.push_section(&format!("{profile}{active}"), &body);
+        }
+        return Ok(output.finish());
+    }
+
+    let original = read_settings_map(host)?;
+    let mut dcm_map = original.clone();
+    if name == "none" {
+        dcm_map.insert(PROFILE_KEY.to_string(), Value::Null);
+    } else if settings.profiles.contains_key(name) {
+        dcm_map.insert(PROFILE_KEY.to_string(), Value::String(name.clone()));
+    } else {
+        let hint = closest_match(name, settings.profiles.keys().map(String::as_str))
+            .map(|name| format!(" Did you mean `{name}`?"))
+            .unwrap_or_default();
+        return Err(DcmError::usage(format!(
+            "Unknown profile `{name}`.{hint} Run `dcm profile list` to see the defined profiles."
+        )));
     }
+    write_settings_map(host, &format!("profile {name}"), &original, dcm_map)?;
+
+    let updated = LayeredSettings::load(host)?;
+    let changed: Vec<String> = schema::SETTINGS
+        .iter()
+        .filter(|spec| settings.value(spec.key) != updated.value(spec.key))
+        .map(|spec| format!("- {}: {}", spec.key, updated.value(spec.key)))
+        .collect();
 
-    let serialized = serde_json::to_string_pretty(&final_root)
-        .map_err(|err| format!("Failed to serialize settings: {err}"))?;
-    fs::write(&path, serialized)
-        .map_err(|err| format!("Failed to write settings file {}: {err}", path.display()))
+    let mut message = match &updated.profile {
+        Some(profile) => format!("Profile `{profile}` is now active."),
+        None => "No profile is active.".to_string(),
+    };
+    if changed.is_empty() {
+        message.push_str(" No effective settings changed.");
+    } else {
+        message.push_str(&format!("\n\nChanged:\n{}", changed.join("\n")));
+    }
+    message.push_str(&format!("\n\n{}", session.restart_summary(host, &updated)));
+    Ok(info_output("DCM Profile", &message))
 }
 
-fn settings_file_path(worktree: &Worktree) -> PathBuf {
-    let root = PathBuf::from(worktree.root_path());
-    root.join(".zed").join("settings.json")
+fn run_history(host: &dyn Host) -> Result<SlashCommandOutput, DcmError> {
+    let entries = history::list(host)?;
+    if entries.is_empty() {
+        return Ok(info_output(
+            "DCM Settings History",
+            "No settings changes have been recorded yet.",
+        ));
+    }
+
+    let mut output = SectionedOutput::default();
+    for entry in &entries {
+        let block = match &entry.dcm {
+            Some(block) => serde_json::to_string_pretty(block).map_err(|err| {
+                DcmError::settings(format!("Failed to serialize settings: {err}"))
+            })?,
+            None => "(no `dcm` block)".to_string(),
+        };
+        output.push_section(
+            &format!("#{} {} (before `{}`)", entry.id, entry.age(), entry.change),
+            &block,
+        );
+    }
+    Ok(output.finish())
 }
 
-fn default_log_path(worktree: &Worktree) -> PathBuf {
-    PathBuf::from(worktree.root_path())
-        .join(".zed")
-        .join("dcm.log")
+/// Restores the `dcm` block from a snapshot, the latest one by default.
+///
+/// Undoing the latest snapshot consumes it, so repeated `dcm undo` walks back
+/// through history. Restoring a specific id keeps it, and the state being
+/// replaced is itself recorded so the restore can be undone.
+fn run_undo(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let entries = history::list(host)?;
+    let entry = match args.first() {
+        Some(raw) => {
+            let id: u64 = raw
+                .trim_start_matches('#')
+                .parse()
+                .map_err(|_| DcmError::usage(format!("Invalid history id `{raw}`")))?;
+            entries.iter().find(|entry| entry.id == id).ok_or_else(|| {
+                DcmError::usage(format!(
+                    "No settings history entry #{id}. Run `dcm history`."
+                ))
+            })?
+        }
+        None => entries.first().ok_or_else(|| {
+            DcmError::usage("Nothing to undo: no settings changes have been recorded.")
+        })?,
+    };
+
+    let path = settings_file_path(host);
+    let previous = read_settings_text(host, &path)?;
+    let block = entry.dcm.clone().map(Value::Object);
+    let contents = jsonc::set_value(&previous, &["dcm"], block.as_ref())
+        .map_err(|err| DcmError::settings(format!("Failed to update {}: {err}", path.display())))?;
+
+    if args.is_empty() {
+        if previous != contents {
+            host.write_file(&path, &contents)?;
+        }
+        history::remove(host, entry.id)?;
+    } else {
+        replace_settings_file(host, &format!("undo #{}", entry.id), &previous, &contents)?;
+    }
+
+    let updated = LayeredSettings::load(host)?;
+    Ok(info_output(
+        "DCM Settings Restored",
+        &format!(
+            "Restored the `dcm` block from #{} ({}, before `{}`). {}",
+            entry.id,
+            entry.age(),
+            entry.change,
+            session.restart_summary(host, &updated)
+        ),
+    ))
+}
+
+fn run_import(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    require_vscode_target("import", args)?;
+
+    let path = host.root_path().join(VSCODE_SETTINGS_FILE);
+    let contents = read_settings_text(host, &path)?;
+    if contents.trim().is_empty() {
+        return Err(DcmError::settings(format!(
+            "No VS Code settings found at {VSCODE_SETTINGS_FILE}"
+        )));
+    }
+    let Value::Object(vscode_settings) = jsonc::parse(&contents)
+        .map_err(|err| DcmError::settings(format!("Invalid JSON in {}: {err}", path.display())))?
+    else {
+        return Err(DcmError::settings(format!(
+            "{VSCODE_SETTINGS_FILE} must contain a JSON object"
+        )));
+    };
+
+    let mut conversion = vscode::import(&vscode_settings);
+    let settings = LayeredSettings::load(host)?;
+    let original = read_settings_map(host)?;
+    let mut dcm_map = original.clone();
+    let mut imported = Vec::new();
+    for (key, value) in conversion.values {
+        let Some(spec) = schema::spec(&key) else {
+            continue;
+        };
+        if settings.is_locked(spec.key) {
+            conversion.skipped.push((
+                format!("dcm.{}", spec.camel_key),
+                format!("locked by {TEAM_SETTINGS_FILE}"),
+            ));
+            continue;
+        }
+        dcm_map.remove(spec.camel_key);
+        imported.push(format!("- {key}: {value}"));
+        dcm_map.insert(key, value);
+    }
+    write_settings_map(host, "import vscode", &original, dcm_map)?;
+
+    let mut output = SectionedOutput::default();
+    output.push_section(
+        &format!("Imported into {PROJECT_SETTINGS_FILE}"),
+        &list_or(&imported, "No `dcm.*` settings were imported."),
+    );
+    push_skipped_section(&mut output, "Not Imported", &conversion.skipped);
+    output.push_section(
+        "Server",
+        &session.restart_summary(host, &LayeredSettings::load(host)?),
+    );
+    Ok(output.finish())
+}
+
+fn run_export(args: &[String], host: &dyn Host) -> Result<SlashCommandOutput, DcmError> {
+    require_vscode_target("export", args)?;
+
+    let conversion = vscode::export(&read_settings_map(host)?);
+    let path = host.root_path().join(VSCODE_SETTINGS_FILE);
+    let previous = read_settings_text(host, &path)?;
+    let mut contents = previous.clone();
+    let mut exported = Vec::new();
+    for (key, value) in &conversion.values {
+        contents = jsonc::set_value(&contents, &[key], Some(value)).map_err(|err| {
+            DcmError::settings(format!("Failed to update {}: {err}", path.display()))
+        })?;
+        exported.push(format!("- {key}: {value}"));
+    }
+
+    if contents != previous {
+        host.write_file(&path, &contents)?;
+    }
+
+    let mut output = SectionedOutput::default();
+    output.push_section(
+        &format!("Exported to {VSCODE_SETTINGS_FILE}"),
+        &list_or(
+            &exported,
+            &format!("{PROJECT_SETTINGS_FILE} has no `dcm` settings to export."),
+        ),
+    );
+    push_skipped_section(&mut output, "Not Exported", &conversion.skipped);
+    Ok(output.finish())
+}
+
+fn require_vscode_target(action: &str, args: &[String]) -> Result<(), DcmError> {
+    match args.first().map(String::as_str) {
+        Some("vscode") => Ok(()),
+        Some(other) => Err(DcmError::usage(format!(
+            "Cannot {action} `{other}` settings. Use vscode."
+        ))),
+        None => Err(DcmError::usage(format!("Usage: dcm {action} vscode"))),
+    }
+}
+
+fn push_skipped_section(output: &mut SectionedOutput, label: &str, skipped: &[(String, String)]) {
+    if skipped.is_empty() {
+        return;
+    }
+    let lines: Vec<String> = skipped
+        .iter()
+        .map(|(key, reason)| format!("- {key}: {reason}"))
+        .collect();
+    output.push_section(label, &lines.join("\n"));
+}
+
+fn list_or(lines: &[String], empty: &str) -> String {
+    if lines.is_empty() {
+        empty.to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+fn run_trust(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let settings = session.resolve(host)?;
+    match args.first().map(String::as_str) {
+        None => Ok(info_output(
+            "DCM Trusted Paths",
+            &trust_summary(host, &settings),
+        )),
+        Some("approve") => {
+            let approved = trust::approve(host, &settings)?;
+            let message = if approved.is_empty() {
+                "Nothing needs approval.".to_string()
+            } else {
+                let lines: Vec<String> = approved
+                    .iter()
+                    .map(|entry| format!("- {}: {}", entry.key, entry.path.display()))
+                    .collect();
+                format!(
+                    "Approved for this worktree:\n{}\nRestart the DCM server to use them.",
+                    lines.join("\n")
+                )
+            };
+            Ok(info_output("DCM Paths Approved", &message))
+        }
+        Some("revoke") => {
+            let removed = trust::revoke(host, &settings)?;
+            Ok(info_output(
+                "DCM Approvals Revoked",
+                &format!("Removed {removed} approval(s) for this worktree."),
+            ))
+        }
+        Some(other) => Err(DcmError::usage(format!(
+            "Unknown trust command `{other}`. Use approve or revoke."
+        ))),
+    }
+}
+
+fn trust_summary(host: &dyn Host, settings: &config::ResolvedSettings) -> String {
+    let entries = trust::review(host, settings);
+    if entries.is_empty() {
+        return "No executable, SDK or log file paths are configured.".to_string();
+    }
+    let mut lines: Vec<String> = entries.iter().map(|entry| entry.render()).collect();
+    if entries.iter().any(|entry| entry.status.needs_approval()) {
+        lines.push("Run `dcm trust approve` to allow the paths marked NOT APPROVED.".to_string());
+    }
+    lines.join("\n")
+}
+
+fn run_doctor(host: &dyn Host, session: &SessionState) -> Result<SlashCommandOutput, DcmError> {
+    let diagnosis = doctor::run_checks(host, session);
+    let mut output = doctor_output(&diagnosis);
+    if LayeredSettings::load(host).is_ok_and(|settings| settings.user.debug_log) {
+        push_debug_log(host, &mut output, &host.root_path());
+    }
+    Ok(output.finish())
+}
+
+fn doctor_output(diagnosis: &Diagnosis) -> SectionedOutput {
+    let mut output = SectionedOutput::default();
+    output.push_section("DCM Doctor", &diagnosis.summary());
+    for check in &diagnosis.checks {
+        let mut body = check.detail.clone();
+        if let Some(hint) = &check.hint {
+            body.push_str(&format!("\nFix: {hint}"));
+        }
+        output.push_section(&format!("[{}] {}", check.status.label(), check.name), &body);
+    }
+    output
+}
+
+/// Writes a redacted Markdown report for DCM support and prints an issue template.
+fn run_support_bundle(
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let env = host.shell_env();
+    let diagnosis = doctor::run_checks(host, session);
+    let (os, arch) = host.platform();
+    let versions = format!(
+        "- Extension: {}\n- DCM: {}\n- Dart SDK: {}\n- Platform: {os:?} {arch:?}",
+        env!("CARGO_PKG_VERSION"),
+        diagnosis.dcm_version.as_deref().unwrap_or("unknown"),
+        diagnosis.dart_version.as_deref().unwrap_or("unknown"),
+    );
+
+    let mut bundle = format!(
+        "# DCM Support Bundle\n\nGenerated at Unix time {}. Home paths and secrets are removed; review before sharing.\n\n## Versions\n{versions}\n\n## Health Check\n{}",
+        unix_timestamp(),
+        demote_headings(&doctor_output(&diagnosis).finish().text)
+    );
+    match run_config(host, session) {
+        Ok(config) => {
+            bundle.push_str(&format!(
+                "\n\n## Configuration\n{}",
+                demote_headings(&config.text)
+            ));
+        }
+        Err(err) => bundle.push_str(&format!("\n\n## Configuration\nUnavailable: {err}")),
+    }
+
+    // Doctor already skips unapproved paths; the log is only read once its
+    // location is trusted too.
+    let untrusted_log = session.resolve(host).ok().and_then(|settings| {
+        trust::review(host, &settings)
+            .into_iter()
+            .find(|entry| {
+                entry.status.needs_approval()
+                    && (entry.key == "log_file_path" || entry.key == "log_directory")
+            })
+            .map(|entry| entry.key)
+    });
+    let log = if let Some(key) = untrusted_log {
+        format!("Not included: `{key}` needs `/dcm trust approve`.")
+    } else {
+        match captured_log_path(host, session) {
+            Ok(log_path) => match tail_lines(host, &log_path, SUPPORT_LOG_LINES, SUPPORT_LOG_BYTES)
+            {
+                Some(tail) => format!(
+                    "Last {SUPPORT_LOG_LINES} lines of {}:\n\n```text\n{}\n```",
+                    log_path.display(),
+                    tail.trim_end()
+                ),
+                None => format!(
+                    "No log at {}. Restart the server and reproduce the problem first.",
+                    log_path.display()
+                ),
+            },
+            Err(err) => err.to_string(),
+        }
+    };
+    bundle.push_str(&format!("\n\n## Server Log\n{log}\n"));
+
+    let path = host.root_path().join(".zed").join(SUPPORT_BUNDLE_FILE_NAME);
+    host.write_file(&path, &scrub_text(&bundle, &env))?;
+
+    let template = format!(
+        "### Describe the problem\n<What happened, and what did you expect?>\n\n### Steps to reproduce\n1. \n\n### Environment\n{versions}\n- Editor: Zed\n\n### Diagnostics\nDoctor: {}\nSupport bundle: attach `.zed/{SUPPORT_BUNDLE_FILE_NAME}`.",
+        diagnosis.summary()
+    );
+    let mut output = SectionedOutput::default();
+    output.push_section(
+        "Support Bundle",
+        &format!(
+            "Wrote {}. Home paths, license keys and tokens were removed, but review it before sharing.",
+            path.display()
+        ),
+    );
+    output.push_section("Issue Template", &scrub_text(&template, &env));
+    Ok(output.finish())
+}
+
+/// Turns `## ` headings into `### ` so embedded output nests under a bundle section.
+fn demote_headings(text: &str) -> String {
+    text.lines()
+        .map(|line| match line.strip_prefix("## ") {
+            Some(rest) => format!("### {rest}"),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn run_probe(host: &dyn Host, session: &SessionState) -> Result<SlashCommandOutput, DcmError> {
+    let settings = session.resolve(host)?;
+    trust::ensure_trusted(host, &settings)?;
+    let report = probe::run(host, &settings)?;
+    let mut output = SectionedOutput::default();
+
+    let outcome = match &report.outcome {
+        ProbeOutcome::Initialized => "The server initialized.".to_string(),
+        ProbeOutcome::InitializeFailed(error) => format!("`initialize` failed: {error}"),
+        ProbeOutcome::NoResponse => "The server exited without answering `initialize`.".to_string(),
+        ProbeOutcome::TimedOut => format!(
+            "The server did not answer within {}s and was stopped.",
+            probe::PROBE_TIMEOUT_SECS
+        ),
+    };
+    let initialize_time = match report.initialize_time {
+        Some(time) => format!("{} ms", time.as_millis()),
+        None => "not measured".to_string(),
+    };
+    let mut lines = vec![
+        outcome,
+        format!("Initialize time (start to first reply): {initialize_time}"),
+        format!(
+            "Session time (start to exit, including shutdown): {} ms",
+            report.session_time.as_millis()
+        ),
+        format!(
+            "Shutdown acknowledged: {}",
+            if report.shutdown_acknowledged {
+                "yes"
+            } else {
+                "no"
+            }
+        ),
+        format!(
+            "Exit status: {}",
+            report
+                .exit_status
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "unknown".to_string())
+        ),
+    ];
+    if let Some(info) = &report.server_info {
+        lines.push(format!("Server: {info}"));
+    }
+    output.push_section("Probe Result", &lines.join("\n"));
+
+    let command = language_server::build_command(&settings)?;
+    output.push_section(
+        "Launch Command",
+        &format!(
+            "{}\n\nRun through a `{}` wrapper that feeds the handshake to stdin, timestamps the first reply and stops the server after {}s.",
+            redacted_command_line(&command),
+            probe::SHELL,
+            probe::PROBE_TIMEOUT_SECS
+        ),
+    );
+
+    if let Some(capabilities) = &report.capabilities {
+        output.push_section("Capabilities", &render_json(capabilities)?);
+    }
+    if !report.messages.is_empty() {
+        output.push_section("Server Messages", &report.messages.join("\n"));
+    }
+    let stderr = report.stderr.trim();
+    output.push_section("Stderr", if stderr.is_empty() { "(empty)" } else { stderr });
+    Ok(output.finish())
+}
+
+/// Runs `dcm analyze` on `args`, or the whole worktree, and lists the issues per file.
+fn run_analyze(
+    args: &[String],
+    host: &dyn Host,
+    session: &SessionState,
+) -> Result<SlashCommandOutput, DcmError> {
+    let settings = session.resolve(host)?;
+    let mut paths = Vec::new();
+    for raw in args {
+        let path = resolve_path(raw, &settings.env, &settings.root_path)?;
+        if !host.is_file(&path) && !host.is_dir(&path) {
+            return Err(DcmError::usage(format!(
+                "No file or folder at {}",
+                path.display()
+            )));
+        }
+        paths.push(path);
+    }
+    trust::ensure_trusted(host, &settings)?;
+    let files = analyze::run(host, &settings, &paths)?;
+
+    let target = if args.is_empty() {
+        "the worktree".to_string()
+    } else {
+        args.join(", ")
+    };
+    if files.is_empty() {
+        return Ok(info_output(
+            "DCM Analyze",
+            &format!("No issues found in {target}."),
+        ));
+    }
+
+    let total: usize = files.iter().map(|file| file.issues.len()).sum();
+    let mut output = SectionedOutput::default();
+    let mut shown = 0;
+    for file in &files {
+        if shown >= ANALYZE_MAX_ISSUES {
+            break;
+        }
+        let lines: Vec<String> = file
+            .issues
+            .iter()
+            .take(ANALYZE_MAX_ISSUES - shown)
+            .map(|issue| {
+                format!(
+                    "- {}:{} {} {}: {}",
+                    issue.line,
+                    issue.column,
+                    issue.severity.label(),
+                    issue.rule_id,
+                    issue.message
+                )
+            })
+            .collect();
+        shown += lines.len();
+        output.push_section(
+            &format!("{} ({} issue(s))", file.path, file.issues.len()),
+            &lines.join("\n"),
+        );
+    }
+    if shown < total {
+        output.push_section(
+            "More Issues",
+            &format!(
+                "{} more of {total} issue(s) are not listed. Run `dcm analyze` on fewer paths to see them.",
+                total - shown
+            ),
+        );
+    }
+    Ok(output.finish())
+}
+
+/// Renders `command` one argument per line with secret values hidden.
+fn redacted_command_line(command: &zed::Command) -> String {
+    let argv: Vec<String> = std::iter::once(command.command.clone())
+        .chain(command.args.iter().map(|arg| redact_arg(arg)))
+        .collect();
+    argv.join(" \\\n  ")
+}
+
+fn run_config(host: &dyn Host, session: &SessionState) -> Result<SlashCommandOutput, DcmError> {
+    let settings = session.resolve(host)?;
+    let mut output = SectionedOutput::default();
+
+    let values = serde_json::to_value(&settings.layered.user)
+        .map_err(|err| DcmError::settings(format!("Failed to serialize settings: {err}")))?;
+    let mut lines = Vec::new();
+    for spec in schema::SETTINGS {
+        let value = values.get(spec.key).cloned().unwrap_or(Value::Null);
+        let locked = if settings.layered.is_locked(spec.key) {
+            ", locked"
+        } else {
+            ""
+        };
+        let requirement = spec
+            .min_dcm_version
+            .map(|version| format!(", needs DCM {version}+"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "{}: {} ({}{locked}{requirement})",
+            spec.key,
+            value,
+            settings.layered.source_of(spec.key).label()
+        ));
+    }
+    lines.push(format!(
+        "resolved executable: {}",
+        path_to_string(&settings.executable_path)?
+    ));
+    if let Some(sdk_path) = &settings.sdk_path {
+        lines.push(format!("resolved SDK: {}", path_to_string(sdk_path)?));
+    }
+    if let Some(profile) = &settings.layered.profile {
+        lines.push(format!("active profile: {profile}"));
+    }
+    lines.push(session.restart_summary(host, &LayeredSettings::load(host)?));
+    for warning in settings.layered.warnings() {
+        lines.push(warning.render());
+    }
+    output.push_section("Effective Settings", &lines.join("\n"));
+
+    output.push_section("Trusted Paths", &trust_summary(host, &settings));
+
+    let command = language_server::build_command(&settings)?;
+    output.push_section(
+        "Launch Command",
+        &format!(
+            "{}\n\nRun through a `{}` wrapper that feeds the handshake to stdin, timestamps the first reply and stops the server after {}s.",
+            redacted_command_line(&command),
+            probe::SHELL,
+            probe::PROBE_TIMEOUT_SECS
+        ),
+    );
+
+    let mut env_lines: Vec<String> = language_server::environment_overrides(&settings)?
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    for (key, value) in &settings.env {
+        if key.starts_with("DCM_") {
+            let value = if is_secret_name(key) {
+                "<redacted>"
+            } else {
+                value.as_str()
+            };
+            env_lines.push(format!("{key}={value} (from shell)"));
+        }
+    }
+    output.push_section("Environment Overrides", &env_lines.join("\n"));
+
+    let init_options = language_server::initialization_options(&settings)?;
+    output.push_section("Initialization Options", &render_json(&init_options)?);
+
+    let workspace = language_server::workspace_configuration(&settings)?;
+    output.push_section("Workspace Configuration", &render_json(&workspace)?);
+
+    if settings.layered.user.debug_log {
+        push_debug_log(host, &mut output, &settings.root_path);
+    }
+
+    Ok(output.finish())
+}
+
+/// Adds the end of the extension debug log as a section.
+fn push_debug_log(fs: &dyn FileSystem, output: &mut SectionedOutput, root: &Path) {
+    let path = debug_log::path(root);
+    let body = tail_lines(fs, &path, DEBUG_LOG_LINES, DEBUG_LOG_BYTES).unwrap_or_else(|| {
+        format!(
+            "Nothing recorded yet in {}. Entries are written when the settings are next resolved.",
+            debug_log::DEBUG_LOG_FILE
+        )
+    });
+    output.push_section("Extension Debug Log", &body);
+}
+
+fn render_json(value: &Value) -> Result<String, DcmError> {
+    serde_json::to_string_pretty(&redact_json(value))
+        .map_err(|err| DcmError::settings(format!("Failed to serialize JSON: {err}")))
+}
+
+fn redact_json(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_secret_name(key) && !value.is_null() {
+                        Value::String("<redacted>".to_string())
+                    } else {
+                        redact_json(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_json).collect()),
+        other => other.clone(),
+    }
+}
+
+fn run_validate(host: &dyn Host) -> Result<SlashCommandOutput, DcmError> {
+    let diagnostics = LayeredSettings::load(host)?.diagnostics;
+    if diagnostics.is_empty() {
+        return Ok(info_output(
+            "DCM Settings",
+            "No problems found in the DCM settings files.",
+        ));
+    }
+
+    let lines: Vec<String> = diagnostics
+        .iter()
+        .map(|d| format!("- {}", d.render()))
+        .collect();
+    Ok(info_output(
+        "DCM Settings",
+        &format!("Found {} problem(s):\n{}", lines.len(), lines.join("\n")),
+    ))
+}
+
+fn run_schema(host: &dyn Host) -> Result<SlashCommandOutput, DcmError> {
+    let path = host.root_path().join(".zed").join(SCHEMA_FILE_NAME);
+    let serialized = serde_json::to_string_pretty(&schema::json_schema())
+        .map_err(|err| DcmError::settings(format!("Failed to serialize settings schema: {err}")))?;
+
+    host.write_file(&path, &(serialized + "\n"))?;
+
+    Ok(info_output(
+        "DCM Settings Schema",
+        &format!(
+            "JSON Schema for the `dcm` settings block written to {}.",
+            path_to_string(&path)?
+        ),
+    ))
+}
+
+fn describe_log_location(host: &dyn Host, session: &SessionState) -> Result<String, DcmError> {
+    let settings = session.resolve(host)?;
+    let read_hint =
+        "Read it with `dcm log tail [lines]`, `dcm log show <pattern>` or `dcm log summary`.";
+    if let Some(dir) = &settings.log_directory {
+        let current = match &settings.log_file_path {
+            Some(path) => format!(" The current session writes to {}.", path_to_string(path)?),
+            None => String::new(),
+        };
+        return Ok(format!(
+            "Session logs: {} (from {} settings).{current} Run `dcm log list` to see them. {read_hint}",
+            path_to_string(dir)?,
+            settings.layered.source_of("log_directory").label()
+        ));
+    }
+    match &settings.log_file_path {
+        Some(path) => Ok(format!(
+            "Log file: {} (from {} settings). {read_hint}",
+            path_to_string(path)?,
+            settings.layered.source_of("log_file_path").label()
+        )),
+        None => Ok(
+            "Log capture is disabled. Run `dcm log capture` to write a log per session to `.zed/dcm-logs/`."
+                .to_string(),
+        ),
+    }
+}
+
+fn help_output() -> SlashCommandOutput {
+    let text = format!(
+        r#"DCM commands:
+- dcm help
+- dcm open [rules|metrics|feedback|logs]
+- dcm toggle [{}]
+- dcm set <key> <value>
+- dcm get [key]
+- dcm unset <key>
+- dcm exclude [add|remove] <folder...>
+- dcm exclude list
+- dcm log [capture|clear]
+- dcm log tail [lines]
+- dcm log show <pattern>
+- dcm log summary
+- dcm log list
+- dcm log prune [all]
+- dcm profile [list|none|<name>]
+- dcm history
+- dcm undo [id]
+- dcm import vscode
+- dcm export vscode
+- dcm trust [approve|revoke]
+- dcm doctor
+- dcm probe
+- dcm analyze [path...]
+- dcm support-bundle
+- dcm config
+- dcm validate
+- dcm schema
+- dcm restart"#,
+        toggle_names().join("|")
+    );
+
+    SlashCommandOutput {
+        text,
+        sections: Vec::new(),
+    }
+}
+
+/// Accumulates titled sections into a single [`SlashCommandOutput`].
+#[derive(Default)]
+struct SectionedOutput {
+    text: String,
+    sections: Vec<SlashCommandOutputSection>,
+}
+
+impl SectionedOutput {
+    fn push_section(&mut self, label: &str, body: &str) {
+        if !self.text.is_empty() {
+            self.text.push_str("\n\n");
+        }
+        let start = self.text.len() as u32;
+        self.text.push_str(&format!("## {label}\n{body}"));
+        self.sections.push(SlashCommandOutputSection {
+            range: zed::Range {
+                start,
+                end: self.text.len() as u32,
+            },
+            label: label.to_string(),
+        });
+    }
+
+    fn finish(self) -> SlashCommandOutput {
+        SlashCommandOutput {
+            text: self.text,
+            sections: self.sections,
+        }
+    }
+}
+
+fn info_output(title: &str, message: &str) -> SlashCommandOutput {
+    SlashCommandOutput {
+        text: format!("{title}: {message}"),
+        sections: vec![SlashCommandOutputSection {
+            range: zed::Range {
+                start: 0,
+                end: title.len() as u32,
+            },
+            label: title.to_string(),
+        }],
+    }
+}
+
+/// Reads the `dcm` block of the project settings file.
+fn read_settings_map(host: &dyn Host) -> Result<Map<String, Value>, DcmError> {
+    let path = settings_file_path(host);
+    parse_dcm_block(&path, &read_settings_text(host, &path)?)
+}
+
+/// Reads the `dcm` block of a settings file, ignoring any problem with it.
+fn read_dcm_block_for_completion(fs: &dyn FileSystem, path: &Path) -> Option<Map<String, Value>> {
+    let contents = fs.read_file(path).ok()?;
+    parse_dcm_block(path, &contents).ok()
+}
+
+fn parse_dcm_block(path: &Path, contents: &str) -> Result<Map<String, Value>, DcmError> {
+    if contents.trim().is_empty() {
+        return Ok(Map::new());
+    }
+
+    let root = jsonc::parse(contents)
+        .map_err(|err| DcmError::settings(format!("Invalid JSON in {}: {err}", path.display())))?;
+    match root {
+        Value::Object(mut map) => match map.remove("dcm") {
+            Some(Value::Object(dcm)) => Ok(dcm),
+            _ => Ok(Map::new()),
+        },
+        _ => Err(DcmError::settings(format!(
+            "Settings file {} must contain a JSON object",
+            path.display()
+        ))),
+    }
+}
+
+/// Applies the keys that differ between `original` and `dcm_map` to the settings file.
+///
+/// The file is re-read right before writing so edits made elsewhere since
+/// [`read_settings_map`] are kept, and only the changed `dcm.*` members are
+/// rewritten in place before the result is swapped in atomically.
+fn write_settings_map(
+    host: &dyn Host,
+    change: &str,
+    original: &Map<String, Value>,
+    dcm_map: Map<String, Value>,
+) -> Result<(), DcmError> {
+    let path = settings_file_path(host);
+    let previous = read_settings_text(host, &path)?;
+    let mut contents = previous.clone();
+
+    let removed = original.keys().filter(|key| !dcm_map.contains_key(*key));
+    for key in removed {
+        contents = jsonc::set_value(&contents, &["dcm", key], None).map_err(|err| {
+            DcmError::settings(format!("Failed to update {}: {err}", path.display()))
+        })?;
+    }
+    for (key, value) in &dcm_map {
+        if original.get(key) == Some(value) {
+            continue;
+        }
+        contents = jsonc::set_value(&contents, &["dcm", key], Some(value)).map_err(|err| {
+            DcmError::settings(format!("Failed to update {}: {err}", path.display()))
+        })?;
+    }
+
+    replace_settings_file(host, change, &previous, &contents)
+}
+
+/// Snapshots `previous` into the settings history, then writes `contents`.
+fn replace_settings_file(
+    host: &dyn Host,
+    change: &str,
+    previous: &str,
+    contents: &str,
+) -> Result<(), DcmError> {
+    if previous == contents {
+        return Ok(());
+    }
+
+    history::record(host, change, previous)?;
+    host.write_file(&settings_file_path(host), contents)
+}
+
+fn read_settings_text(host: &dyn Host, path: &Path) -> Result<String, DcmError> {
+    if host.is_file(path) {
+        host.read_file(path)
+    } else {
+        Ok(String::new())
+    }
+}
+
+fn settings_file_path(host: &dyn Host) -> PathBuf {
+    host.root_path().join(PROJECT_SETTINGS_FILE)
+}
+
+fn default_log_dir(host: &dyn Host) -> PathBuf {
+    host.root_path().join(".zed").join("dcm-logs")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::fake::FakeHost;
+
+    fn run_in(host: &FakeHost, args: &[&str]) -> Result<SlashCommandOutput, DcmError> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        run(args, Some(host), &SessionState::default())
+    }
+
+    fn labels(args: &[&str]) -> Vec<String> {
+        labels_in(&FakeHost::new(), args)
+    }
+
+    fn labels_in(host: &FakeHost, args: &[&str]) -> Vec<String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        complete(&args, Some(&host.root_path()), host)
+            .into_iter()
+            .map(|completion| completion.label)
+            .collect()
+    }
+
+    fn project() -> FakeHost {
+        FakeHost::new()
+            .with_file("lib/main.dart", "")
+            .with_file("lib/src/app.dart", "")
+            .with_file("lib/README.md", "")
+            .with_dir("build")
+            .with_dir(".dart_tool")
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "excluded_folders": ["build", "lib/gen"] } }"#,
+            )
+    }
+
+    #[test]
+    fn support_bundle_skips_unapproved_paths() {
+        let host = FakeHost::new().with_file("/opt/tools/dcm", "").with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{ "dcm": { "executable_path": "/opt/tools/dcm", "log_file_path": "/var/tmp/dcm.log" } }"#,
+        );
+
+        run_in(&host, &["support-bundle"]).unwrap();
+        assert!(host.commands.borrow().is_empty());
+        let bundle = host.file(".zed/dcm-support-bundle.md").unwrap();
+        assert!(bundle.contains("Not run: `executable_path` needs `/dcm trust approve`."));
+        assert!(bundle.contains("Not included: `log_file_path` needs `/dcm trust approve`."));
+    }
+
+    #[test]
+    fn log_prune_refuses_an_unapproved_directory() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_file("/var/tmp/dcm/dcm-20260101-000001.log", "")
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "log_directory": "/var/tmp/dcm" } }"#,
+            );
+
+        let err = run_in(&host, &["log", "prune", "all"]).unwrap_err();
+        assert_eq!(err.code(), "DCM202");
+        assert!(host.is_file(Path::new("/var/tmp/dcm/dcm-20260101-000001.log")));
+    }
+
+    fn captured_log() -> FakeHost {
+        FakeHost::new()
+            .with_file(
+                "dcm.log",
+                "[Info] starting\n[Error] no config\n[Info] analyzing\n[Error] timeout\n",
+            )
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "log_file_path": "dcm.log" } }"#,
+            )
+    }
+
+    #[test]
+    fn log_tail_shows_the_last_lines() {
+        let output = run_in(&captured_log(), &["log", "tail", "2"]).unwrap();
+        assert!(output.text.contains("Last 2 lines"));
+        assert!(output.text.contains("[Info] analyzing\n[Error] timeout"));
+        assert!(!output.text.contains("no config"));
+    }
+
+    #[test]
+    fn log_show_filters_by_pattern() {
+        let output = run_in(&captured_log(), &["log", "show", "error"]).unwrap();
+        assert!(output.text.contains("2 lines matching `error`"));
+        assert!(output.text.contains("[Error] no config\n[Error] timeout"));
+        assert!(!output.text.contains("analyzing"));
+    }
+
+    #[test]
+    fn probe_is_unavailable_on_windows() {
+        let host = FakeHost::new()
+            .with_os(zed::Os::Windows)
+            .with_binary("dcm", "/usr/local/bin/dcm");
+
+        let err = run_in(&host, &["probe"]).unwrap_err();
+        assert_eq!(err.code(), "DCM101");
+        assert!(host.commands.borrow().is_empty());
+    }
+
+    #[test]
+    fn export_vscode_keeps_other_settings() {
+        let host = FakeHost::new()
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "show_unused_code": true, "excluded_folders": ["build"] } }"#,
+            )
+            .with_file(
+                VSCODE_SETTINGS_FILE,
+                "{\n  // Editor\n  \"editor.formatOnSave\": true,\n  \"dcm.showUnusedCode\": false\n}\n",
+            );
+
+        run_in(&host, &["export", "vscode"]).unwrap();
+        let exported = host.file(VSCODE_SETTINGS_FILE).unwrap();
+        assert!(exported.contains("// Editor"));
+        assert_eq!(
+            jsonc::parse(&exported).unwrap(),
+            serde_json::json!({
+                "editor.formatOnSave": true,
+                "dcm.showUnusedCode": true,
+                "dcm.excludedFolders": ["build"],
+            })
+        );
+    }
+
+    #[test]
+    fn import_vscode_round_trips_an_export() {
+        let host = FakeHost::new().with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{ "dcm": { "show_unused_files": true, "log_max_size_mb": 20 } }"#,
+        );
+        run_in(&host, &["export", "vscode"]).unwrap();
+        host.write_file(
+            &host.root_path().join(PROJECT_SETTINGS_FILE),
+            r#"{ "theme": "One", "dcm": { "showUnusedFiles": false } }"#,
+        )
+        .unwrap();
+
+        run_in(&host, &["import", "vscode"]).unwrap();
+        assert_eq!(
+            jsonc::parse(&host.file(PROJECT_SETTINGS_FILE).unwrap()).unwrap(),
+            serde_json::json!({
+                "theme": "One",
+                "dcm": { "show_unused_files": true, "log_max_size_mb": 20 },
+            })
+        );
+    }
+
+    #[test]
+    fn help_lists_toggles_from_settings_table() {
+        assert!(help_output().text.contains(
+            "- dcm toggle [new-version|unused-code|unused-files|baseline|old-formatter|only-opened]"
+        ));
+    }
+
+    #[test]
+    fn toggle_completions_come_from_settings_table() {
+        let args = vec!["toggle".to_string(), "un".to_string()];
+        let labels: Vec<String> = complete(&args, None, &FakeHost::new())
+            .into_iter()
+            .map(|c| c.label)
+            .collect();
+        assert_eq!(labels, ["unused-code", "unused-files"]);
+    }
+
+    #[test]
+    fn toggle_flips_the_project_setting_and_records_history() {
+        let host = FakeHost::new();
+        run_in(&host, &["toggle", "unused-code"]).unwrap();
+        assert!(LayeredSettings::load(&host).unwrap().user.show_unused_code);
+
+        run_in(&host, &["toggle", "unused-code"]).unwrap();
+        assert!(!LayeredSettings::load(&host).unwrap().user.show_unused_code);
+
+        let entries = history::list(&host).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].change, "set show_unused_code false");
+    }
+
+    #[test]
+    fn toggle_keeps_the_rest_of_the_settings_file() {
+        let host = FakeHost::new().with_file(
+            PROJECT_SETTINGS_FILE,
+            "{\n  // Editor font\n  \"buffer_font_size\": 14\n}\n",
+        );
+        run_in(&host, &["toggle", "baseline"]).unwrap();
+
+        let contents = host.file(PROJECT_SETTINGS_FILE).unwrap();
+        assert!(contents.contains("// Editor font"));
+        assert!(contents.contains("\"buffer_font_size\": 14"));
+        assert!(LayeredSettings::load(&host).unwrap().user.disable_baseline);
+    }
+
+    #[test]
+    fn unknown_toggle_is_a_usage_error() {
+        let err = run_in(&FakeHost::new(), &["toggle", "everything"]).unwrap_err();
+        assert_eq!(err.code(), "DCM101");
+    }
+
+    #[test]
+    fn locked_settings_cannot_be_toggled() {
+        let host = FakeHost::new().with_file(
+            TEAM_SETTINGS_FILE,
+            r#"{ "dcm": { "show_unused_files": true }, "locked": ["show_unused_files"] }"#,
+        );
+        assert!(run_in(&host, &["toggle", "unused-files"]).is_err());
+        assert_eq!(host.file(PROJECT_SETTINGS_FILE), None);
+    }
+
+    #[test]
+    fn set_then_undo_restores_the_previous_value() {
+        let host = FakeHost::new();
+        run_in(&host, &["set", "excluded_folders", "build", "gen"]).unwrap();
+        assert_eq!(
+            LayeredSettings::load(&host).unwrap().user.excluded_folders,
+            ["build", "gen"]
+        );
+
+        run_in(&host, &["undo"]).unwrap();
+        let settings = LayeredSettings::load(&host).unwrap();
+        assert!(settings.user.excluded_folders.is_empty());
+        assert!(history::list(&host).unwrap().is_empty());
+    }
+
+    #[test]
+    fn commands_need_a_worktree() {
+        let err = run(vec!["config".to_string()], None, &SessionState::default()).unwrap_err();
+        assert_eq!(err.code(), "DCM101");
+    }
+
+    #[test]
+    fn top_level_completions_filter_by_prefix() {
+        assert_eq!(labels(&["tr"]), ["trust"]);
+        assert_eq!(labels(&["log", "s"]), ["show", "summary"]);
+        assert_eq!(labels(&["log", "p"]), ["prune"]);
+        assert_eq!(labels(&["log", "l"]), ["list"]);
+    }
+
+    #[test]
+    fn set_completions_offer_keys_then_values() {
+        assert!(labels(&["set", "show_"]).contains(&"show_unused_code".to_string()));
+        assert_eq!(labels(&["set", "show_unused_code", ""]), ["true", "false"]);
+        assert_eq!(
+            labels(&["set", "dart_sdk_path", ""]),
+            ["./", "~/", "$HOME/"]
+        );
+    }
+
+    #[test]
+    fn analyze_lists_issues_per_file() {
+        let report = r#"{ "records": [{
+            "path": "/work/app/lib/main.dart",
+            "issues": [{
+                "ruleId": "avoid-dynamic",
+                "severity": "warning",
+                "message": "Avoid using dynamic type.",
+                "codeSpan": { "start": { "line": 2, "column": 10 } }
+            }]
+        }] }"#;
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_file("lib/main.dart", "")
+            .with_output(
+                &[
+                    "/usr/local/bin/dcm",
+                    "analyze",
+                    "--reporter=json",
+                    "/work/app/lib",
+                ],
+                1,
+                report,
+            );
+
+        let output = run_in(&host, &["analyze", "lib"]).unwrap();
+        assert_eq!(output.sections[0].label, "lib/main.dart (1 issue(s))");
+        assert!(output
+            .text
+            .contains("- 2:10 warning avoid-dynamic: Avoid using dynamic type."));
+    }
+
+    #[test]
+    fn analyze_reports_unreadable_output() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_output(
+                &[
+                    "/usr/local/bin/dcm",
+                    "analyze",
+                    "--reporter=json",
+                    "/work/app",
+                ],
+                1,
+                "License expired.\n",
+            );
+        let err = run_in(&host, &["analyze"]).unwrap_err();
+        assert_eq!(err.code(), "DCM600");
+    }
+
+    #[test]
+    fn exclude_add_completes_folders_below_the_worktree() {
+        let host = project();
+        assert_eq!(
+            labels_in(&host, &["exclude", "add", ""]),
+            ["build/", "lib/"]
+        );
+        assert_eq!(labels_in(&host, &["exclude", "add", "lib/"]), ["lib/src/"]);
+        assert_eq!(
+            labels_in(&host, &["exclude", "add", "."]),
+            [".dart_tool/", ".zed/"]
+        );
+    }
+
+    #[test]
+    fn exclude_remove_completes_the_project_entries() {
+        assert_eq!(
+            labels_in(&project(), &["exclude", "remove", ""]),
+            ["build", "lib/gen"]
+        );
+    }
+
+    #[test]
+    fn exclude_add_edits_only_the_project_array() {
+        let host = project().with_file(
+            TEAM_SETTINGS_FILE,
+            r#"{ "excluded_folders": ["generated"] }"#,
+        );
+
+        run_in(&host, &["exclude", "add", "./build/", "/work/app/tool"]).unwrap();
+        let project: Value = jsonc::parse(&host.file(PROJECT_SETTINGS_FILE).unwrap()).unwrap();
+        assert_eq!(
+            project["dcm"]["excluded_folders"],
+            serde_json::json!(["build", "lib/gen", "tool"])
+        );
+    }
+
+    #[test]
+    fn exclude_remove_ignores_folders_from_other_layers() {
+        let host = project().with_file(
+            TEAM_SETTINGS_FILE,
+            r#"{ "excluded_folders": ["generated"] }"#,
+        );
+
+        let err = run_in(&host, &["exclude", "remove", "generated"]).unwrap_err();
+        assert_eq!(err.code(), "DCM101");
+        run_in(&host, &["exclude", "remove", "build/"]).unwrap();
+        let project: Value = jsonc::parse(&host.file(PROJECT_SETTINGS_FILE).unwrap()).unwrap();
+        assert_eq!(
+            project["dcm"]["excluded_folders"],
+            serde_json::json!(["lib/gen"])
+        );
+    }
+
+    #[test]
+    fn analyze_completes_folders_and_dart_files() {
+        let host = project();
+        assert_eq!(
+            labels_in(&host, &["analyze", "lib/"]),
+            ["lib/main.dart", "lib/src/"]
+        );
+        let completions = complete(
+            &["analyze".to_string(), "lib/m".to_string()],
+            Some(&host.root_path()),
+            &host,
+        );
+        assert_eq!(completions[0].new_text, "analyze lib/main.dart");
+    }
 }
@@ -1,12 +1,64 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
-use serde_json;
-use zed_extension_api::{EnvVars, Worktree};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use zed_extension_api::EnvVars;
 
-use crate::utils::{canonicalize_if_possible, path_to_string, resolve_path};
+use crate::error::DcmError;
+use crate::host::{FileSystem, Host};
+use crate::jsonc;
+use crate::schema::{self, SettingsDiagnostic, Severity};
+use crate::utils::{
+    canonicalize_if_possible, closest_match, env_value, path_to_string, resolve_path,
+};
 
-#[derive(Debug, Clone, Deserialize)]
+/// Path of the project settings file, relative to the worktree root.
+pub const PROJECT_SETTINGS_FILE: &str = ".zed/settings.json";
+
+/// Path of the committed team settings file, relative to the worktree root.
+pub const TEAM_SETTINGS_FILE: &str = ".dcm/zed.json";
+
+/// Key in the `dcm` block naming the active profile.
+pub const PROFILE_KEY: &str = "profile";
+
+/// Key in the `dcm` block holding named profiles.
+pub const PROFILES_KEY: &str = "profiles";
+
+/// Environment variables that override a single setting, applied last.
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("DCM_EXECUTABLE_PATH", "executable_path"),
+    ("DCM_DART_SDK_PATH", "dart_sdk_path"),
+    ("DCM_LOG_FILE_PATH", "log_file_path"),
+];
+
+/// Where the effective value of a setting came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SettingSource {
+    Default,
+    User,
+    Team,
+    Project,
+    Profile,
+    Env,
+}
+
+impl SettingSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            SettingSource::Default => "default",
+            SettingSource::User => "user",
+            SettingSource::Team => "team",
+            SettingSource::Project => "project",
+            SettingSource::Profile => "profile",
+            SettingSource::Env => "env",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct UserSettings {
     pub executable_path: Option<String>,
@@ -19,6 +71,10 @@ pub struct UserSettings {
     pub analyze_only_opened: bool,
     pub excluded_folders: Vec<String>,
     pub log_file_path: Option<String>,
+    pub log_directory: Option<String>,
+    pub log_max_files: u64,
+    pub log_max_size_mb: u64,
+    pub debug_log: bool,
 }
 
 impl Default for UserSettings {
@@ -34,115 +90,720 @@ impl Default for UserSettings {
             analyze_only_opened: false,
             excluded_folders: Vec::new(),
             log_file_path: None,
+            log_directory: None,
+            log_max_files: 10,
+            log_max_size_mb: 50,
+            debug_log: false,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ResolvedSettings {
-    pub user: UserSettings,
+    /// The merged settings these paths were resolved from.
+    pub layered: LayeredSettings,
     pub executable_path: PathBuf,
     pub sdk_path: Option<PathBuf>,
     pub root_path: PathBuf,
     pub excluded_folders: Vec<PathBuf>,
+    /// The server log file. In per-session mode this is the current session's
+    /// file once the server has started, and `None` before.
     pub log_file_path: Option<PathBuf>,
+    pub log_directory: Option<PathBuf>,
     pub env: EnvVars,
 }
 
+/// The merged `dcm` block together with per-key provenance.
+///
+/// Unlike [`ResolvedSettings`], nothing is looked up on disk or on PATH, so
+/// this is what slash commands use to read and edit settings.
+#[derive(Debug, Clone, Default)]
+pub struct LayeredSettings {
+    pub user: UserSettings,
+    pub sources: BTreeMap<String, SettingSource>,
+    pub locked: BTreeSet<String>,
+    pub diagnostics: Vec<SettingsDiagnostic>,
+    /// Name of the active profile, if one is selected and defined.
+    pub profile: Option<String>,
+    /// Every profile defined across the settings files, by name.
+    pub profiles: BTreeMap<String, Map<String, Value>>,
+}
+
+impl LayeredSettings {
+    pub fn load(host: &dyn Host) -> Result<Self, DcmError> {
+        load_user_settings(host, &host.shell_env(), &mut Vec::new())
+    }
+
+    /// Returns the effective value of `key` as JSON.
+    pub fn value(&self, key: &str) -> Value {
+        serde_json::to_value(&self.user)
+            .ok()
+            .and_then(|mut values| values.get_mut(key).map(Value::take))
+            .unwrap_or(Value::Null)
+    }
+
+    /// Returns where the effective value of `key` came from.
+    pub fn source_of(&self, key: &str) -> SettingSource {
+        self.sources
+            .get(key)
+            .copied()
+            .unwrap_or(SettingSource::Default)
+    }
+
+    /// Returns `true` when the team settings file enforces `key`.
+    pub fn is_locked(&self, key: &str) -> bool {
+        self.locked.contains(key)
+    }
+
+    /// Returns the warnings found while validating the settings files.
+    pub fn warnings(&self) -> impl Iterator<Item = &SettingsDiagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+    }
+}
+
+/// Settings committed to the repository for the whole team.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TeamSettingsFile {
+    dcm: Map<String, Value>,
+    locked: Vec<String>,
+}
+
 impl ResolvedSettings {
-    pub fn from_worktree(worktree: &Worktree) -> Result<Self, String> {
-        let env = worktree.shell_env();
-        let root_path = PathBuf::from(worktree.root_path());
-        let user = load_user_settings(worktree)?;
+    /// Resolves the settings of the worktree behind `host`, appending each
+    /// decision to `trace` for the extension debug log.
+    pub fn from_host(host: &dyn Host, trace: &mut Vec<String>) -> Result<Self, DcmError> {
+        let env = host.shell_env();
+        let root_path = host.root_path();
+        let layered = load_user_settings(host, &env, trace)?;
+        let user = &layered.user;
 
-        let executable_path = resolve_executable(worktree, &user, &env, &root_path)?;
-        let sdk_path = resolve_optional_path(user.dart_sdk_path.as_deref(), &env, &root_path)?;
-        let log_file_path = resolve_optional_path(user.log_file_path.as_deref(), &env, &root_path)?;
-        let excluded_folders = resolve_excluded_folders(&user, &env, &root_path)?;
+        let errors: Vec<String> = layered
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(SettingsDiagnostic::render)
+            .collect();
+        if !errors.is_empty() {
+            return Err(DcmError::settings(format!(
+                "Invalid DCM settings:\n{}",
+                errors.join("\n")
+            )));
+        }
+
+        let executable_path = resolve_executable(host, user, &env, &root_path, trace)?;
+        let sdk_path = resolve_optional_path(
+            host,
+            "dart_sdk_path",
+            user.dart_sdk_path.as_deref(),
+            &env,
+            &root_path,
+            trace,
+        )?;
+        let log_directory = resolve_optional_path(
+            host,
+            "log_directory",
+            user.log_directory.as_deref(),
+            &env,
+            &root_path,
+            trace,
+        )?;
+        let log_file_path = match log_directory {
+            Some(_) => None,
+            None => resolve_optional_path(
+                host,
+                "log_file_path",
+                user.log_file_path.as_deref(),
+                &env,
+                &root_path,
+                trace,
+            )?,
+        };
+        let excluded_folders = resolve_excluded_folders(host, user, &env, &root_path)?;
 
         Ok(Self {
-            user,
+            layered,
             executable_path,
             sdk_path,
             root_path,
             excluded_folders,
             log_file_path,
+            log_directory,
             env,
         })
     }
 }
 
-fn load_user_settings(worktree: &Worktree) -> Result<UserSettings, String> {
-    let contents = match worktree.read_text_file(".zed/settings.json") {
-        Ok(contents) => contents,
-        Err(_) => return Ok(UserSettings::default()),
+/// Loads the `dcm` block from every settings layer and merges them.
+///
+/// Precedence, lowest to highest: built-in defaults, the user-level Zed
+/// settings file, the team `.dcm/zed.json`, the project `.zed/settings.json`,
+/// the active profile, then `DCM_*` environment variables. Keys listed under
+/// `locked` in the team file are re-applied last so nothing can override them.
+/// Each layer replaces whole keys; arrays are not concatenated.
+///
+/// Profiles may be defined under `profiles` in any settings file; the last
+/// file to set `profile` chooses which one is active.
+fn load_user_settings(
+    host: &dyn Host,
+    env: &EnvVars,
+    trace: &mut Vec<String>,
+) -> Result<LayeredSettings, DcmError> {
+    let mut merged = Map::new();
+    let mut sources = BTreeMap::new();
+    let mut diagnostics = Vec::new();
+    let mut profiles = Profiles::default();
+
+    match user_settings_path(env) {
+        Some(path) => match host.read_file(&path) {
+            Ok(contents) => {
+                trace.push(format!("read user settings {}", path.display()));
+                let label = path_to_string(&path)?;
+                let mut layer = parse_dcm_block(&contents, &label)?;
+                profiles.absorb(&mut layer, &label, &mut diagnostics);
+                let layer = schema::normalize(layer, &label, &mut diagnostics);
+                merge_layer(&mut merged, &mut sources, layer, SettingSource::User);
+            }
+            Err(_) => trace.push(format!("no user settings at {}", path.display())),
+        },
+        None => trace
+            .push("no user settings: none of XDG_CONFIG_HOME, APPDATA or HOME is set".to_string()),
+    }
+
+    let mut team = match host.read_text_file(TEAM_SETTINGS_FILE) {
+        Ok(contents) => {
+            trace.push(format!("read team settings {TEAM_SETTINGS_FILE}"));
+            parse_team_settings(&contents)?
+        }
+        Err(_) => {
+            trace.push(format!("no team settings at {TEAM_SETTINGS_FILE}"));
+            TeamSettingsFile::default()
+        }
     };
+    profiles.absorb(&mut team.dcm, TEAM_SETTINGS_FILE, &mut diagnostics);
+    team.dcm = schema::normalize(team.dcm, TEAM_SETTINGS_FILE, &mut diagnostics);
+    merge_layer(
+        &mut merged,
+        &mut sources,
+        team.dcm.clone(),
+        SettingSource::Team,
+    );
+
+    if let Ok(contents) = host.read_text_file(PROJECT_SETTINGS_FILE) {
+        trace.push(format!("read project settings {PROJECT_SETTINGS_FILE}"));
+        let mut layer = parse_dcm_block(&contents, PROJECT_SETTINGS_FILE)?;
+        profiles.absorb(&mut layer, PROJECT_SETTINGS_FILE, &mut diagnostics);
+        let layer = schema::normalize(layer, PROJECT_SETTINGS_FILE, &mut diagnostics);
+        merge_layer(&mut merged, &mut sources, layer, SettingSource::Project);
+    }
+
+    let profile = profiles.active_layer(&mut diagnostics);
+    if let Some((name, layer)) = &profile {
+        trace.push(format!("applied profile `{name}`"));
+        merge_layer(
+            &mut merged,
+            &mut sources,
+            layer.clone(),
+            SettingSource::Profile,
+        );
+    }
+
+    let overrides = env_layer(env);
+    for key in overrides.keys() {
+        trace.push(format!("`{key}` overridden by the environment"));
+    }
+    // `DCM_LOG_FILE_PATH` names a single log file, which only takes effect
+    // without per-session logs.
+    if overrides.contains_key("log_file_path") && merged.remove("log_directory").is_some() {
+        sources.remove("log_directory");
+        trace.push("`log_directory` ignored because `DCM_LOG_FILE_PATH` is set".to_string());
+    }
+    merge_layer(&mut merged, &mut sources, overrides, SettingSource::Env);
+
+    let mut locked = BTreeSet::new();
+    for key in &team.locked {
+        if !schema::is_known(key) {
+            return Err(DcmError::settings(format!(
+                "Unknown setting `{key}` listed under `locked` in {TEAM_SETTINGS_FILE}"
+            )));
+        }
+        let Some(value) = team.dcm.get(key) else {
+            return Err(DcmError::settings(format!(
+                "Locked setting `{key}` has no value in the `dcm` block of {TEAM_SETTINGS_FILE}"
+            )));
+        };
+        trace.push(format!("`{key}` locked by {TEAM_SETTINGS_FILE}"));
+        merged.insert(key.clone(), value.clone());
+        sources.insert(key.clone(), SettingSource::Team);
+        locked.insert(key.clone());
+    }
+
+    for spec in schema::SETTINGS {
+        sources
+            .entry(spec.key.to_string())
+            .or_insert(SettingSource::Default);
+    }
+
+    let user = serde_json::from_value(Value::Object(merged))
+        .map_err(|err| DcmError::settings(format!("Invalid `dcm` settings block: {err}")))?;
+    Ok(LayeredSettings {
+        user,
+        sources,
+        locked,
+        diagnostics,
+        profile: profile.map(|(name, _)| name),
+        profiles: profiles.defined,
+    })
+}
+
+/// Profile definitions and selection gathered while reading settings layers.
+#[derive(Default)]
+struct Profiles {
+    defined: BTreeMap<String, Map<String, Value>>,
+    active: Option<(String, String)>,
+}
+
+impl Profiles {
+    /// Removes the profile keys from `layer`, recording what they define.
+    fn absorb(
+        &mut self,
+        layer: &mut Map<String, Value>,
+        label: &str,
+        diagnostics: &mut Vec<SettingsDiagnostic>,
+    ) {
+        match layer.remove(PROFILES_KEY) {
+            Some(Value::Object(defined)) => {
+                for (name, profile) in defined {
+                    match profile {
+                        Value::Object(profile) => {
+                            self.defined.insert(name, profile);
+                        }
+                        _ => diagnostics.push(SettingsDiagnostic {
+                            severity: Severity::Error,
+                            file: label.to_string(),
+                            message: format!("profile `{name}` must be an object"),
+                        }),
+                    }
+                }
+            }
+            Some(Value::Null) | None => {}
+            Some(_) => diagnostics.push(SettingsDiagnostic {
+                severity: Severity::Error,
+                file: label.to_string(),
+                message: format!("`{PROFILES_KEY}` must be an object of named profiles"),
+            }),
+        }
+
+        match layer.remove(PROFILE_KEY) {
+            Some(Value::String(name)) => self.active = Some((name, label.to_string())),
+            Some(Value::Null) => self.active = None,
+            None => {}
+            Some(_) => diagnostics.push(SettingsDiagnostic {
+                severity: Severity::Error,
+                file: label.to_string(),
+                message: format!("`{PROFILE_KEY}` must be a profile name"),
+            }),
+        }
+    }
 
+    /// Returns the validated settings of the selected profile.
+    fn active_layer(
+        &self,
+        diagnostics: &mut Vec<SettingsDiagnostic>,
+    ) -> Option<(String, Map<String, Value>)> {
+        let (name, label) = self.active.as_ref()?;
+        match self.defined.get(name) {
+            Some(profile) => {
+                let file = format!("{label} (profile `{name}`)");
+                let layer = schema::normalize(profile.clone(), &file, diagnostics);
+                Some((name.clone(), layer))
+            }
+            None => {
+                let hint = closest_match(name, self.defined.keys().map(String::as_str))
+                    .map(|name| format!(" Did you mean `{name}`?"))
+                    .unwrap_or_default();
+                diagnostics.push(SettingsDiagnostic {
+                    severity: Severity::Warning,
+                    file: label.clone(),
+                    message: format!("profile `{name}` is not defined and is ignored.{hint}"),
+                });
+                None
+            }
+        }
+    }
+}
+
+/// Hashes everything settings resolution depends on: the raw contents of each
+/// settings file and the worktree shell environment.
+pub fn settings_fingerprint(host: &dyn Host, env: &EnvVars) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    user_settings_path(env)
+        .and_then(|path| host.read_file(&path).ok())
+        .hash(&mut hasher);
+    host.read_text_file(TEAM_SETTINGS_FILE)
+        .ok()
+        .hash(&mut hasher);
+    host.read_text_file(PROJECT_SETTINGS_FILE)
+        .ok()
+        .hash(&mut hasher);
+    env.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the location of the user-level Zed settings file.
+pub fn user_settings_path(env: &EnvVars) -> Option<PathBuf> {
+    if let Some(config_home) = env_value(env, "XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(config_home).join("zed").join("settings.json"));
+    }
+    if let Some(app_data) = env_value(env, "APPDATA") {
+        return Some(PathBuf::from(app_data).join("Zed").join("settings.json"));
+    }
+    env_value(env, "HOME").map(|home| {
+        PathBuf::from(home)
+            .join(".config")
+            .join("zed")
+            .join("settings.json")
+    })
+}
+
+fn parse_dcm_block(contents: &str, label: &str) -> Result<Map<String, Value>, DcmError> {
     if contents.trim().is_empty() {
-        return Ok(UserSettings::default());
+        return Ok(Map::new());
     }
 
-    let root: serde_json::Value = serde_json::from_str(&contents)
-        .map_err(|err| format!("Failed to parse .zed/settings.json: {err}"))?;
+    let root = jsonc::parse(contents)
+        .map_err(|err| DcmError::settings(format!("Failed to parse {label}: {err}")))?;
 
     match root.get("dcm") {
-        Some(value) => serde_json::from_value(value.clone())
-            .map_err(|err| format!("Invalid `dcm` settings block: {err}")),
-        None => Ok(UserSettings::default()),
+        Some(Value::Object(map)) => Ok(map.clone()),
+        Some(Value::Null) | None => Ok(Map::new()),
+        Some(_) => Err(DcmError::settings(format!(
+            "Invalid `dcm` settings block in {label}: expected an object"
+        ))),
+    }
+}
+
+fn parse_team_settings(contents: &str) -> Result<TeamSettingsFile, DcmError> {
+    if contents.trim().is_empty() {
+        return Ok(TeamSettingsFile::default());
+    }
+
+    let root = jsonc::parse(contents).map_err(|err| {
+        DcmError::settings(format!("Failed to parse {TEAM_SETTINGS_FILE}: {err}"))
+    })?;
+    serde_json::from_value(root).map_err(|err| {
+        DcmError::settings(format!(
+            "Invalid team settings in {TEAM_SETTINGS_FILE}: {err}"
+        ))
+    })
+}
+
+fn env_layer(env: &EnvVars) -> Map<String, Value> {
+    let mut layer = Map::new();
+    for (var, key) in ENV_OVERRIDES {
+        if let Some(value) = env_value(env, var) {
+            if !value.trim().is_empty() {
+                layer.insert((*key).to_string(), Value::String(value));
+            }
+        }
+    }
+    layer
+}
+
+fn merge_layer(
+    merged: &mut Map<String, Value>,
+    sources: &mut BTreeMap<String, SettingSource>,
+    layer: Map<String, Value>,
+    source: SettingSource,
+) {
+    for (key, value) in layer {
+        sources.insert(key.clone(), source);
+        merged.insert(key, value);
     }
 }
 
 fn resolve_executable(
-    worktree: &Worktree,
+    host: &dyn Host,
     user: &UserSettings,
     env: &EnvVars,
     root_path: &Path,
-) -> Result<PathBuf, String> {
+    trace: &mut Vec<String>,
+) -> Result<PathBuf, DcmError> {
     if let Some(raw_path) = user.executable_path.as_deref() {
-        let path = canonicalize_if_possible(resolve_path(raw_path, env, root_path)?);
-        if path.exists() {
+        let path = canonicalize_if_possible(host, resolve_path(raw_path, env, root_path)?);
+        if host.is_file(&path) {
+            trace.push(format!(
+                "executable_path `{raw_path}` resolved to {}",
+                path.display()
+            ));
             return Ok(path);
         }
-        return Err(format!(
-            "Configured DCM executable path does not exist: {}",
-            path_to_string(&path)?
+        trace.push(format!(
+            "executable_path `{raw_path}` resolved to missing {}",
+            path.display()
         ));
+        return Err(DcmError::ExecutableMissing(path));
     }
 
-    match worktree.which("dcm") {
+    match host.which("dcm") {
         Some(path) => {
+            trace.push(format!(
+                "executable_path unset; found `dcm` on PATH at {path}"
+            ));
             Ok(PathBuf::from(path))
         }
-        None => Err("Unable to locate `dcm` executable. Set `dcm.executable_path` in settings or ensure it is available on PATH.".to_string()),
+        None => {
+            trace.push(format!(
+                "executable_path unset; `dcm` not found on PATH {}",
+                env_value(env, "PATH").unwrap_or_default()
+            ));
+            Err(DcmError::ExecutableNotFound)
+        }
     }
 }
 
 fn resolve_optional_path(
+    host: &dyn Host,
+    key: &str,
     raw: Option<&str>,
     env: &EnvVars,
     root_path: &Path,
-) -> Result<Option<PathBuf>, String> {
+    trace: &mut Vec<String>,
+) -> Result<Option<PathBuf>, DcmError> {
     match raw {
         Some(value) if !value.trim().is_empty() => {
-            let path = canonicalize_if_possible(resolve_path(value, env, root_path)?);
+            let path = canonicalize_if_possible(host, resolve_path(value, env, root_path)?);
+            let state = if host.is_file(&path) || host.is_dir(&path) {
+                ""
+            } else {
+                "missing "
+            };
+            trace.push(format!(
+                "{key} `{value}` resolved to {state}{}",
+                path.display()
+            ));
             Ok(Some(path))
         }
-        _ => Ok(None),
+        _ => {
+            trace.push(format!("{key} unset"));
+            Ok(None)
+        }
     }
 }
 
-fn resolve_excluded_folders(
+pub fn resolve_excluded_folders(
+    fs: &dyn FileSystem,
     user: &UserSettings,
     env: &EnvVars,
     root_path: &Path,
-) -> Result<Vec<PathBuf>, String> {
+) -> Result<Vec<PathBuf>, DcmError> {
     let mut folders = Vec::new();
     for folder in &user.excluded_folders {
         if folder.trim().is_empty() {
             continue;
         }
-        let resolved = canonicalize_if_possible(resolve_path(folder, env, root_path)?);
+        let resolved = canonicalize_if_possible(fs, resolve_path(folder, env, root_path)?);
         folders.push(resolved);
     }
     Ok(folders)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::fake::FakeHost;
+    use crate::language_server;
+
+    const USER_SETTINGS: &str = "/home/dev/.config/zed/settings.json";
+
+    fn resolve(host: &FakeHost) -> Result<ResolvedSettings, DcmError> {
+        ResolvedSettings::from_host(host, &mut Vec::new())
+    }
+
+    fn with_dcm() -> FakeHost {
+        FakeHost::new().with_binary("dcm", "/usr/local/bin/dcm")
+    }
+
+    #[test]
+    fn later_layers_replace_earlier_ones() {
+        let host = with_dcm()
+            .with_file(
+                USER_SETTINGS,
+                r#"{ "dcm": { "show_unused_code": true, "show_unused_files": true } }"#,
+            )
+            .with_file(
+                TEAM_SETTINGS_FILE,
+                r#"{ "dcm": { "show_unused_files": false, "excluded_folders": ["build"] } }"#,
+            )
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "excluded_folders": ["gen"] } }"#,
+            );
+
+        let settings = resolve(&host).unwrap();
+        assert!(settings.layered.user.show_unused_code);
+        assert!(!settings.layered.user.show_unused_files);
+        assert_eq!(settings.excluded_folders, [PathBuf::from("/work/app/gen")]);
+        assert_eq!(
+            settings.layered.source_of("show_unused_code"),
+            SettingSource::User
+        );
+        assert_eq!(
+            settings.layered.source_of("show_unused_files"),
+            SettingSource::Team
+        );
+        assert_eq!(
+            settings.layered.source_of("excluded_folders"),
+            SettingSource::Project
+        );
+        assert_eq!(
+            settings.layered.source_of("disable_baseline"),
+            SettingSource::Default
+        );
+    }
+
+    #[test]
+    fn active_profile_applies_over_project_settings() {
+        let host = with_dcm().with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{
+                "dcm": {
+                    "show_unused_code": false,
+                    "profile": "ci",
+                    "profiles": { "ci": { "show_unused_code": true } }
+                }
+            }"#,
+        );
+
+        let settings = resolve(&host).unwrap();
+        assert_eq!(settings.layered.profile.as_deref(), Some("ci"));
+        assert!(settings.layered.user.show_unused_code);
+        assert_eq!(
+            settings.layered.source_of("show_unused_code"),
+            SettingSource::Profile
+        );
+    }
+
+    #[test]
+    fn environment_overrides_settings_files() {
+        let host = FakeHost::new()
+            .with_file("/opt/dcm/dcm", "")
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "executable_path": "/missing/dcm" } }"#,
+            )
+            .with_env("DCM_EXECUTABLE_PATH", "/opt/dcm/dcm");
+
+        let settings = resolve(&host).unwrap();
+        assert_eq!(settings.executable_path, PathBuf::from("/opt/dcm/dcm"));
+        assert_eq!(
+            settings.layered.source_of("executable_path"),
+            SettingSource::Env
+        );
+    }
+
+    #[test]
+    fn log_file_variable_wins_over_log_directory() {
+        let host = with_dcm()
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "log_directory": ".zed/dcm-logs" } }"#,
+            )
+            .with_env("DCM_LOG_FILE_PATH", "/work/app/dcm.log");
+
+        let settings = resolve(&host).unwrap();
+        assert_eq!(
+            settings.log_file_path,
+            Some(PathBuf::from("/work/app/dcm.log"))
+        );
+        assert_eq!(settings.log_directory, None);
+        assert_eq!(
+            settings.layered.source_of("log_directory"),
+            SettingSource::Default
+        );
+    }
+
+    #[test]
+    fn locked_team_settings_cannot_be_overridden() {
+        let host = with_dcm()
+            .with_file(
+                TEAM_SETTINGS_FILE,
+                r#"{ "dcm": { "disable_baseline": true }, "locked": ["disable_baseline"] }"#,
+            )
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "disable_baseline": false } }"#,
+            );
+
+        let settings = resolve(&host).unwrap();
+        assert!(settings.layered.user.disable_baseline);
+        assert!(settings.layered.is_locked("disable_baseline"));
+        assert_eq!(
+            settings.layered.source_of("disable_baseline"),
+            SettingSource::Team
+        );
+    }
+
+    #[test]
+    fn executable_is_found_on_path() {
+        let settings = resolve(&with_dcm()).unwrap();
+        assert_eq!(
+            settings.executable_path,
+            PathBuf::from("/usr/local/bin/dcm")
+        );
+    }
+
+    #[test]
+    fn missing_executables_are_reported() {
+        assert_eq!(
+            resolve(&FakeHost::new()).unwrap_err(),
+            DcmError::ExecutableNotFound
+        );
+
+        let host = FakeHost::new().with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{ "dcm": { "executable_path": "/opt/dcm/dcm" } }"#,
+        );
+        assert_eq!(
+            resolve(&host).unwrap_err(),
+            DcmError::ExecutableMissing(PathBuf::from("/opt/dcm/dcm"))
+        );
+    }
+
+    #[test]
+    fn invalid_settings_files_fail_to_resolve() {
+        let host = with_dcm().with_file(PROJECT_SETTINGS_FILE, r#"{ "dcm": [] }"#);
+        assert_eq!(resolve(&host).unwrap_err().code(), "DCM100");
+    }
+
+    #[test]
+    fn fingerprint_follows_settings_files() {
+        let host = with_dcm();
+        let before = settings_fingerprint(&host, &host.shell_env());
+        let host = host.with_file(PROJECT_SETTINGS_FILE, r#"{ "dcm": {} }"#);
+        assert_ne!(before, settings_fingerprint(&host, &host.shell_env()));
+    }
+
+    #[test]
+    fn server_command_is_built_from_resolved_settings() {
+        let host = with_dcm().with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{ "dcm": { "show_unused_code": true, "excluded_folders": ["build"] } }"#,
+        );
+
+        let command = language_server::build_command(&resolve(&host).unwrap()).unwrap();
+        assert_eq!(command.command, "/usr/local/bin/dcm");
+        assert_eq!(
+            command.args,
+            [
+                "start-server",
+                "--root-folder=/work/app",
+                "--show-unused-code",
+                "--excluded-folders=/work/app/build",
+                "--client=zed",
+            ]
+        );
+    }
+}
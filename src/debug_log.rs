@@ -0,0 +1,132 @@
+//! The extension's own debug log, enabled with the `debug_log` setting.
+//!
+//! Records what the extension decided while resolving settings and starting
+//! the server: which settings files it read, which executable and SDK
+//! candidates it tried, and the final command. Server starts do not run
+//! `dcm --version`; the versions are only added when `/dcm doctor` probes
+//! them. The server's traffic goes to `log_file_path` or `log_directory`
+//! instead.
+
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::Command;
+
+use crate::error::DcmError;
+use crate::host::FileSystem;
+use crate::log_sessions;
+use crate::utils::{redact_arg, unix_timestamp};
+
+/// Path of the debug log, relative to the worktree root.
+pub const DEBUG_LOG_FILE: &str = ".zed/dcm-extension.log";
+
+/// Size past which the log is moved to `<file>.old` and started afresh.
+const MAX_BYTES: u64 = 1024 * 1024;
+
+pub fn path(root: &Path) -> PathBuf {
+    root.join(DEBUG_LOG_FILE)
+}
+
+/// Appends a timestamped entry describing `event`, with one indented line per detail.
+///
+/// Failures are ignored: the debug log must never be the reason the server
+/// does not start.
+pub fn record(fs: &dyn FileSystem, root: &Path, event: &str, details: &[String]) {
+    let path = path(root);
+    if fs
+        .metadata(&path)
+        .is_some_and(|metadata| metadata.len > MAX_BYTES)
+    {
+        let _ = fs.rename(&path, &path.with_extension("log.old"));
+    }
+
+    let mut entry = format!(
+        "[{}] {event}\n",
+        log_sessions::format_timestamp(unix_timestamp())
+    );
+    for detail in details {
+        entry.push_str("  ");
+        entry.push_str(detail);
+        entry.push('\n');
+    }
+    let _ = fs.append_file(&path, &entry);
+}
+
+/// Describes the command the server starts with, hiding secret values and
+/// listing only the names of the environment variables.
+pub fn launch_details(command: &Command) -> Vec<String> {
+    let argv: Vec<String> = std::iter::once(command.command.clone())
+        .chain(command.args.iter().map(|arg| redact_arg(arg)))
+        .collect();
+    let env_keys: Vec<&str> = command.env.iter().map(|(key, _)| key.as_str()).collect();
+    vec![
+        format!("argv: {}", argv.join(" ")),
+        format!(
+            "env keys: {}",
+            if env_keys.is_empty() {
+                "none".to_string()
+            } else {
+                env_keys.join(", ")
+            }
+        ),
+    ]
+}
+
+/// Renders `err` on one line with its code.
+pub fn error_line(err: &DcmError) -> String {
+    format!("error {}: {}", err.code(), err.message().replace('\n', " "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::fake::FakeHost;
+
+    const ROOT: &str = "/work/app";
+
+    #[test]
+    fn appends_entries_with_indented_details() {
+        let host = FakeHost::new();
+        record(&host, Path::new(ROOT), "first", &[]);
+        record(
+            &host,
+            Path::new(ROOT),
+            "second",
+            &["a".to_string(), "b".to_string()],
+        );
+
+        let log = host.file(DEBUG_LOG_FILE).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with('[') && lines[0].ends_with("] first"));
+        assert!(lines[1].ends_with("] second"));
+        assert_eq!(&lines[2..], ["  a", "  b"]);
+    }
+
+    #[test]
+    fn rotates_past_the_size_limit() {
+        let host = FakeHost::new().with_file(DEBUG_LOG_FILE, &"x".repeat(MAX_BYTES as usize + 1));
+        record(&host, Path::new(ROOT), "fresh", &[]);
+
+        assert_eq!(
+            host.file(".zed/dcm-extension.log.old").unwrap().len(),
+            MAX_BYTES as usize + 1
+        );
+        assert!(host.file(DEBUG_LOG_FILE).unwrap().ends_with("] fresh\n"));
+    }
+
+    #[test]
+    fn launch_details_hide_secrets_and_environment_values() {
+        let command = Command::new("/usr/bin/dcm")
+            .args(["start-server", "--license-key=ABC-123"])
+            .env("DART_SDK", "/opt/sdk");
+
+        assert_eq!(
+            launch_details(&command),
+            [
+                "argv: /usr/bin/dcm start-server --license-key=<redacted>",
+                "env keys: DART_SDK",
+            ]
+        );
+        assert_eq!(launch_details(&Command::new("dcm"))[1], "env keys: none");
+    }
+}
@@ -0,0 +1,705 @@
+//! The checks behind `/dcm doctor`.
+
+use std::path::Path;
+
+use serde_json::Value;
+use zed_extension_api as zed;
+
+use crate::config::{LayeredSettings, ResolvedSettings};
+use crate::debug_log;
+use crate::error::DcmError;
+use crate::host::Host;
+use crate::language_server;
+use crate::log_sessions;
+use crate::schema::{self, Severity};
+use crate::session::SessionState;
+use crate::trust::{self, TrustedPath};
+use crate::utils::path_to_string;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "pass",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    /// A failure whose detail and fix come from `err`.
+    fn from_error(name: &'static str, err: &DcmError) -> Self {
+        Self::fail(
+            name,
+            format!("[{}] {}", err.code(), err.message()),
+            err.hint(),
+        )
+    }
+}
+
+/// The checks that ran, plus the versions they discovered.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnosis {
+    pub checks: Vec<Check>,
+    pub dcm_version: Option<String>,
+    pub dart_version: Option<String>,
+}
+
+impl Diagnosis {
+    pub fn summary(&self) -> String {
+        let count = |status: CheckStatus| {
+            self.checks
+                .iter()
+                .filter(|check| check.status == status)
+                .count()
+        };
+        format!(
+            "{} passed, {} warnings, {} failed",
+            count(CheckStatus::Pass),
+            count(CheckStatus::Warn),
+            count(CheckStatus::Fail)
+        )
+    }
+}
+
+/// Runs every check. Later checks are skipped when the settings cannot be resolved.
+///
+/// The checks change nothing on disk, and never run an executable or point
+/// DCM at an SDK that still needs `/dcm trust approve`.
+pub fn run_checks(host: &dyn Host, session: &SessionState) -> Diagnosis {
+    let mut checks = vec![check_settings_files(host)];
+
+    let settings = match session.resolve(host) {
+        Ok(settings) => settings,
+        Err(err) => {
+            let name = match err {
+                DcmError::ExecutableNotFound | DcmError::ExecutableMissing(_) => "Executable",
+                _ => "Settings",
+            };
+            checks.push(Check::from_error(name, &err));
+            return Diagnosis {
+                checks,
+                ..Diagnosis::default()
+            };
+        }
+    };
+
+    let pending: Vec<TrustedPath> = trust::review(host, &settings)
+        .into_iter()
+        .filter(|entry| entry.status.needs_approval())
+        .collect();
+    let blocked: Vec<&str> = pending
+        .iter()
+        .map(|entry| entry.key)
+        .filter(|key| *key == "executable_path" || *key == "dart_sdk_path")
+        .collect();
+
+    let (executable, version) = if blocked.is_empty() {
+        check_executable(host, &settings)
+    } else {
+        (not_run("Executable", &blocked), None)
+    };
+    checks.push(executable);
+    checks.push(check_trust(&pending));
+    let (sdk, dart_version) = check_sdk(host, &settings);
+    checks.push(sdk);
+    if !blocked.is_empty() {
+        checks.push(not_run("License", &blocked));
+    } else if version.is_some() {
+        checks.push(check_license(host, &settings));
+    }
+    checks.push(check_excluded_folders(host, &settings));
+    checks.push(check_log_path(host, &settings, &pending));
+    checks.push(check_flag_support(&settings, version.as_deref()));
+    let diagnosis = Diagnosis {
+        checks,
+        dcm_version: version,
+        dart_version,
+    };
+    if settings.layered.user.debug_log {
+        record_versions(host, &settings, &diagnosis);
+    }
+    diagnosis
+}
+
+/// Adds the probed versions and any failed checks to the extension debug log.
+fn record_versions(host: &dyn Host, settings: &ResolvedSettings, diagnosis: &Diagnosis) {
+    let mut details = vec![
+        format!(
+            "{} --version: {}",
+            settings.executable_path.display(),
+            diagnosis.dcm_version.as_deref().unwrap_or("unknown")
+        ),
+        format!(
+            "Dart SDK version: {}",
+            diagnosis.dart_version.as_deref().unwrap_or("unknown")
+        ),
+    ];
+    for check in &diagnosis.checks {
+        if check.status != CheckStatus::Pass {
+            details.push(format!(
+                "{} {}: {}",
+                check.status.label(),
+                check.name,
+                check.detail
+            ));
+        }
+    }
+    debug_log::record(host, &settings.root_path, "doctor", &details);
+}
+
+fn check_settings_files(host: &dyn Host) -> Check {
+    const NAME: &str = "Settings files";
+    let settings = match LayeredSettings::load(host) {
+        Ok(settings) => settings,
+        Err(err) => {
+            return Check::from_error(NAME, &err);
+        }
+    };
+
+    let errors: Vec<String> = settings
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.render())
+        .collect();
+    if !errors.is_empty() {
+        return Check::fail(
+            NAME,
+            errors.join("\n"),
+            "Run `dcm validate` and correct the listed values.",
+        );
+    }
+
+    let warnings: Vec<String> = settings.warnings().map(|w| w.render()).collect();
+    if warnings.is_empty() {
+        Check::pass(NAME, "All settings files parse and validate.")
+    } else {
+        Check::warn(NAME, warnings.join("\n"), "Run `dcm validate` for details.")
+    }
+}
+
+/// Checks that the executable runs, returning its version when it does.
+fn check_executable(host: &dyn Host, settings: &ResolvedSettings) -> (Check, Option<String>) {
+    const NAME: &str = "Executable";
+    let path = settings.executable_path.display().to_string();
+    let hint = "Reinstall DCM, or point `dcm.executable_path` at a working `dcm` binary.";
+
+    match run_dcm(host, settings, &["--version"]) {
+        Ok(stdout) => match parse_version(&stdout) {
+            Some(version) => (
+                Check::pass(NAME, format!("{path} (DCM {version})")),
+                Some(version),
+            ),
+            None => (
+                Check::warn(
+                    NAME,
+                    format!("{path} ran, but reported no version: {}", stdout.trim()),
+                    hint,
+                ),
+                None,
+            ),
+        },
+        Err(err) => (
+            Check::fail(
+                NAME,
+                format!("{path} could not be run: {}", err.message()),
+                hint,
+            ),
+            None,
+        ),
+    }
+}
+
+/// A check skipped because the paths it would use are not approved.
+fn not_run(name: &'static str, keys: &[&str]) -> Check {
+    let keys: Vec<String> = keys.iter().map(|key| format!("`{key}`")).collect();
+    Check::warn(
+        name,
+        format!(
+            "Not run: {} needs `/dcm trust approve`.",
+            keys.join(" and ")
+        ),
+        "Review the paths under Trusted paths, then run `/dcm trust approve` and `/dcm doctor` again.",
+    )
+}
+
+fn check_trust(pending: &[TrustedPath]) -> Check {
+    const NAME: &str = "Trusted paths";
+    if pending.is_empty() {
+        Check::pass(NAME, "No paths need approval.")
+    } else {
+        let lines: Vec<String> = pending.iter().map(TrustedPath::render).collect();
+        Check::fail(
+            NAME,
+            lines.join("\n"),
+            "Review the paths, then run `dcm trust approve` and restart the server.",
+        )
+    }
+}
+
+/// Checks the Dart SDK, returning its version when it can be read.
+fn check_sdk(host: &dyn Host, settings: &ResolvedSettings) -> (Check, Option<String>) {
+    const NAME: &str = "Dart SDK";
+    if let Some(sdk) = &settings.sdk_path {
+        if !host.is_dir(sdk) {
+            let check = Check::fail(
+                NAME,
+                format!("{} does not exist", sdk.display()),
+                "Fix `dcm.dart_sdk_path` or unset it to use the Dart SDK on PATH.",
+            );
+            return (check, None);
+        }
+        return match host.read_file(&sdk.join("version")) {
+            Ok(version) => {
+                let version = version.trim().to_string();
+                let check = Check::pass(NAME, format!("{} (Dart {version})", sdk.display()));
+                (check, Some(version))
+            }
+            Err(_) => {
+                let check = Check::warn(
+                    NAME,
+                    format!("{} has no `version` file", sdk.display()),
+                    "Point `dcm.dart_sdk_path` at the root of a Dart SDK, not its `bin` folder.",
+                );
+                (check, None)
+            }
+        };
+    }
+
+    let Some(dart) = host.which("dart") else {
+        let check = Check::warn(
+            NAME,
+            "No `dart_sdk_path` is set and `dart` is not on PATH.",
+            "Install the Dart or Flutter SDK, or set `dcm.dart_sdk_path`.",
+        );
+        return (check, None);
+    };
+    let command = zed::process::Command::new(dart.clone())
+        .arg("--version")
+        .envs(settings.env.clone());
+    match host.run(&command) {
+        Ok(output) if output.status == Some(0) => {
+            // `dart --version` prints to stderr on older SDKs.
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let version = parse_version(&text);
+            let label = version.as_deref().unwrap_or("unknown version");
+            let check = Check::pass(NAME, format!("{dart} on PATH (Dart {label})"));
+            (check, version)
+        }
+        _ => {
+            let check = Check::warn(
+                NAME,
+                format!("{dart} is on PATH but `dart --version` failed"),
+                "Repair the Dart installation, or set `dcm.dart_sdk_path`.",
+            );
+            (check, None)
+        }
+    }
+}
+
+fn check_license(host: &dyn Host, settings: &ResolvedSettings) -> Check {
+    const NAME: &str = "License";
+    let hint = "Activate DCM with `dcm activate --license-key=<key>` in a terminal.";
+    match run_dcm(host, settings, &["license"]) {
+        Ok(stdout) => {
+            let summary = stdout.lines().next().unwrap_or_default().trim().to_string();
+            let lower = stdout.to_lowercase();
+            if lower.contains("expired") || lower.contains("not activated") {
+                Check::fail(NAME, summary, hint)
+            } else {
+                Check::pass(NAME, summary)
+            }
+        }
+        Err(err) => Check::warn(
+            NAME,
+            format!("Could not read the license state: {}", err.message()),
+            hint,
+        ),
+    }
+}
+
+fn check_excluded_folders(host: &dyn Host, settings: &ResolvedSettings) -> Check {
+    const NAME: &str = "Excluded folders";
+    if settings.excluded_folders.is_empty() {
+        return Check::pass(NAME, "No folders are excluded.");
+    }
+
+    let missing: Vec<String> = settings
+        .excluded_folders
+        .iter()
+        .filter(|folder| !host.is_dir(folder))
+        .map(|folder| format!("- {}", folder.display()))
+        .collect();
+    if missing.is_empty() {
+        Check::pass(
+            NAME,
+            format!(
+                "All {} excluded folders exist.",
+                settings.excluded_folders.len()
+            ),
+        )
+    } else {
+        Check::warn(
+            NAME,
+            format!("Missing:\n{}", missing.join("\n")),
+            "Remove stale entries with `dcm exclude remove <folder>`.",
+        )
+    }
+}
+
+/// Checks the log location from its metadata and permissions, without
+/// creating or writing anything.
+fn check_log_path(host: &dyn Host, settings: &ResolvedSettings, pending: &[TrustedPath]) -> Check {
+    let (name, key, path) = match (&settings.log_directory, &settings.log_file_path) {
+        (Some(dir), _) => ("Log directory", "log_directory", dir),
+        (None, Some(path)) => ("Log file", "log_file_path", path),
+        (None, None) => return Check::pass("Log file", "Server logging is off."),
+    };
+    if pending.iter().any(|entry| entry.key == key) {
+        return Check::warn(
+            name,
+            format!("Not checked: `{key}` needs `/dcm trust approve`."),
+            "Review the paths under Trusted paths, then run `/dcm trust approve`.",
+        );
+    }
+    let hint = format!("Fix the permissions or change `dcm.{key}`.");
+
+    match host.metadata(path) {
+        Some(metadata) if metadata.is_dir != (key == "log_directory") => Check::fail(
+            name,
+            format!(
+                "{} is a {}",
+                path.display(),
+                if metadata.is_dir { "folder" } else { "file" }
+            ),
+            format!("Change `dcm.{key}`."),
+        ),
+        Some(metadata) if metadata.readonly => {
+            Check::fail(name, format!("{} is read-only", path.display()), hint)
+        }
+        Some(_) if key == "log_directory" => Check::pass(
+            name,
+            format!(
+                "{} is writable ({} session logs)",
+                path.display(),
+                log_sessions::list(host, path).len()
+            ),
+        ),
+        Some(_) => Check::pass(name, format!("{} is writable", path.display())),
+        None => check_log_parent(host, name, key, path, hint),
+    }
+}
+
+/// Checks where a missing log file or folder would be created.
+fn check_log_parent(
+    host: &dyn Host,
+    name: &'static str,
+    key: &str,
+    path: &Path,
+    hint: String,
+) -> Check {
+    // The server creates the log file but not its folder; the extension
+    // creates `log_directory` and its parents when the server starts.
+    let parent = if key == "log_directory" {
+        path.ancestors()
+            .skip(1)
+            .find(|dir| host.metadata(dir).is_some())
+    } else {
+        path.parent().filter(|dir| host.is_dir(dir))
+    };
+    match parent.and_then(|dir| Some((dir, host.metadata(dir)?))) {
+        Some((dir, metadata)) if metadata.readonly => Check::fail(
+            name,
+            format!(
+                "{} cannot be created: {} is read-only",
+                path.display(),
+                dir.display()
+            ),
+            hint,
+        ),
+        Some((_, metadata)) if metadata.is_dir => Check::pass(
+            name,
+            format!("{} will be created when the server starts", path.display()),
+        ),
+        _ => Check::fail(
+            name,
+            format!("{} does not exist", path.parent().unwrap_or(path).display()),
+            format!("Create the folder or change `dcm.{key}`."),
+        ),
+    }
+}
+
+fn check_flag_support(settings: &ResolvedSettings, version: Option<&str>) -> Check {
+    const NAME: &str = "Flag support";
+    let values = serde_json::to_value(&settings.layered.user).unwrap_or(Value::Null);
+    let in_use: Vec<_> = schema::SETTINGS
+        .iter()
+        .filter(|spec| spec.min_dcm_version.is_some())
+        .filter(|spec| values.get(spec.key) != Some(&spec.default.to_value()))
+        .collect();
+    if in_use.is_empty() {
+        return Check::pass(NAME, "No version-dependent settings are in use.");
+    }
+
+    let Some(version) = version else {
+        return Check::warn(
+            NAME,
+            "The DCM version is unknown, so version-dependent settings were not checked.",
+            "Fix the executable check above first.",
+        );
+    };
+    let unsupported: Vec<String> = in_use
+        .iter()
+        .filter_map(|spec| {
+            let required = spec.min_dcm_version?;
+            (!version_at_least(version, required))
+                .then(|| format!("- {} needs DCM {required}+", spec.key))
+        })
+        .collect();
+    if unsupported.is_empty() {
+        Check::pass(
+            NAME,
+            format!("DCM {version} supports every setting in use."),
+        )
+    } else {
+        Check::fail(
+            NAME,
+            format!("DCM {version}:\n{}", unsupported.join("\n")),
+            "Upgrade DCM, or unset the settings listed above.",
+        )
+    }
+}
+
+/// Runs the configured executable with `args`, returning stdout on success.
+fn run_dcm(
+    host: &dyn Host,
+    settings: &ResolvedSettings,
+    args: &[&str],
+) -> Result<String, DcmError> {
+    let executable = path_to_string(&settings.executable_path)?;
+    let command = zed::process::Command::new(executable)
+        .args(args.iter().copied())
+        .envs(language_server::build_environment(settings)?);
+    let output = host.run(&command)?;
+    if output.status == Some(0) {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(DcmError::cli_output(
+            format!("dcm {}", args.join(" ")),
+            format!(
+                "exit status {}: {}",
+                output
+                    .status
+                    .map(|code| code.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                stderr.trim()
+            ),
+        ))
+    }
+}
+
+/// Finds the first `x.y.z` version number in `text`.
+pub fn parse_version(text: &str) -> Option<String> {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find(|word| word.split('.').filter(|part| !part.is_empty()).count() >= 3)
+        .map(|word| word.trim_matches('.').to_string())
+}
+
+/// Compares dotted version numbers numerically.
+pub fn version_at_least(version: &str, required: &str) -> bool {
+    let parts = |value: &str| -> Vec<u64> {
+        value
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parts(version) >= parts(required)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PROJECT_SETTINGS_FILE;
+    use crate::host::fake::FakeHost;
+    use crate::host::FileSystem;
+
+    fn resolved(host: &FakeHost) -> ResolvedSettings {
+        ResolvedSettings::from_host(host, &mut Vec::new()).unwrap()
+    }
+
+    fn check<'a>(diagnosis: &'a Diagnosis, name: &str) -> &'a Check {
+        diagnosis
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn unapproved_executables_are_not_run() {
+        let host = FakeHost::new().with_file("/opt/tools/dcm", "").with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{ "dcm": { "executable_path": "/opt/tools/dcm" } }"#,
+        );
+
+        let diagnosis = run_checks(&host, &SessionState::default());
+        assert!(host.commands.borrow().is_empty());
+        assert_eq!(check(&diagnosis, "Executable").status, CheckStatus::Warn);
+        assert!(check(&diagnosis, "License")
+            .detail
+            .contains("`executable_path` needs `/dcm trust approve`"));
+        assert_eq!(check(&diagnosis, "Trusted paths").status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn settings_errors_are_not_reported_as_executable_problems() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "show_unused_code": "yes" } }"#,
+            );
+
+        let diagnosis = run_checks(&host, &SessionState::default());
+        assert_eq!(check(&diagnosis, "Settings").status, CheckStatus::Fail);
+        assert!(diagnosis
+            .checks
+            .iter()
+            .all(|check| check.name != "Executable"));
+    }
+
+    #[test]
+    fn log_checks_change_nothing_on_disk() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "log_directory": ".zed/dcm-logs" } }"#,
+            );
+
+        let diagnosis = run_checks(&host, &SessionState::default());
+        let log = check(&diagnosis, "Log directory");
+        assert_eq!(log.status, CheckStatus::Pass);
+        assert!(log
+            .detail
+            .ends_with("will be created when the server starts"));
+        assert!(!host.is_dir(Path::new("/work/app/.zed/dcm-logs")));
+    }
+
+    #[test]
+    fn read_only_log_files_fail() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_file("dcm.log", "")
+            .with_readonly("dcm.log")
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "log_file_path": "dcm.log" } }"#,
+            );
+
+        let diagnosis = run_checks(&host, &SessionState::default());
+        let log = check(&diagnosis, "Log file");
+        assert_eq!(log.status, CheckStatus::Fail);
+        assert_eq!(log.detail, "/work/app/dcm.log is read-only");
+    }
+
+    #[test]
+    fn unapproved_log_locations_are_not_checked() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_file(
+                PROJECT_SETTINGS_FILE,
+                r#"{ "dcm": { "log_directory": "/var/tmp/dcm" } }"#,
+            );
+
+        let diagnosis = run_checks(&host, &SessionState::default());
+        assert!(check(&diagnosis, "Log directory")
+            .detail
+            .starts_with("Not checked"));
+    }
+
+    #[test]
+    fn executable_check_reports_the_dcm_version() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_output(
+                &["/usr/local/bin/dcm", "--version"],
+                0,
+                "DCM version: 1.30.2\n",
+            );
+
+        let (check, version) = check_executable(&host, &resolved(&host));
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(version.as_deref(), Some("1.30.2"));
+    }
+
+    #[test]
+    fn expired_license_fails() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/local/bin/dcm")
+            .with_output(
+                &["/usr/local/bin/dcm", "license"],
+                0,
+                "License expired on 2026-01-01\n",
+            );
+
+        let check = check_license(&host, &resolved(&host));
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(
+            *host.commands.borrow(),
+            [vec![
+                "/usr/local/bin/dcm".to_string(),
+                "license".to_string()
+            ]]
+        );
+    }
+}
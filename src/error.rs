@@ -0,0 +1,172 @@
+//! The failures the extension reports, each with a stable code and a fix.
+//!
+//! Errors cross into Zed as strings, so [`DcmError`] renders the same way in
+//! slash command output, the language server status and the debug log:
+//!
+//! ```text
+//! [DCM201] Configured DCM executable path does not exist: /opt/dcm/dcm
+//! Fix: Point `dcm.executable_path` at the DCM executable, or unset it to use `dcm` from PATH.
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DcmError {
+    /// A settings file cannot be parsed, or holds values that are not allowed.
+    Settings(String),
+    /// Slash command arguments that cannot be understood.
+    Usage(String),
+    /// `executable_path` is unset and `dcm` is not on PATH.
+    ExecutableNotFound,
+    /// `executable_path` points at a file that does not exist.
+    ExecutableMissing(PathBuf),
+    /// Paths set by worktree files that have not been approved, already rendered.
+    Untrusted(Vec<String>),
+    /// `dart_sdk_path` does not point at a Dart SDK.
+    Sdk(String),
+    /// Reading, writing or removing a file or folder failed.
+    Filesystem {
+        action: &'static str,
+        path: PathBuf,
+        message: String,
+    },
+    /// A program could not be started.
+    Process { program: String, message: String },
+    /// A DCM command failed, or printed output that cannot be understood.
+    CliOutput { command: String, message: String },
+}
+
+impl DcmError {
+    pub fn settings(message: impl Into<String>) -> Self {
+        DcmError::Settings(message.into())
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        DcmError::Usage(message.into())
+    }
+
+    /// A failed filesystem operation, e.g. `DcmError::filesystem("create", dir, err)`.
+    pub fn filesystem(action: &'static str, path: &Path, err: impl fmt::Display) -> Self {
+        DcmError::Filesystem {
+            action,
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn process(program: impl Into<String>, err: impl fmt::Display) -> Self {
+        DcmError::Process {
+            program: program.into(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn cli_output(command: impl Into<String>, message: impl Into<String>) -> Self {
+        DcmError::CliOutput {
+            command: command.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Identifies the kind of failure. Codes never change meaning, so they can
+    /// be searched for in issues and documentation.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DcmError::Settings(_) => "DCM100",
+            DcmError::Usage(_) => "DCM101",
+            DcmError::ExecutableNotFound => "DCM200",
+            DcmError::ExecutableMissing(_) => "DCM201",
+            DcmError::Untrusted(_) => "DCM202",
+            DcmError::Sdk(_) => "DCM300",
+            DcmError::Filesystem { .. } => "DCM400",
+            DcmError::Process { .. } => "DCM500",
+            DcmError::CliOutput { .. } => "DCM600",
+        }
+    }
+
+    /// What went wrong, without the code or the fix.
+    pub fn message(&self) -> String {
+        match self {
+            DcmError::Settings(message) | DcmError::Usage(message) | DcmError::Sdk(message) => {
+                message.clone()
+            }
+            DcmError::ExecutableNotFound => {
+                "Unable to locate the `dcm` executable on PATH.".to_string()
+            }
+            DcmError::ExecutableMissing(path) => format!(
+                "Configured DCM executable path does not exist: {}",
+                path.display()
+            ),
+            DcmError::Untrusted(paths) => format!(
+                "DCM settings in this worktree point outside known locations:\n{}",
+                paths
+                    .iter()
+                    .map(|path| format!("- {path}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            DcmError::Filesystem {
+                action,
+                path,
+                message,
+            } => format!("Failed to {action} {}: {message}", path.display()),
+            DcmError::Process { program, message } => {
+                format!("Failed to run {program}: {message}")
+            }
+            DcmError::CliOutput { command, message } => format!("`{command}` failed: {message}"),
+        }
+    }
+
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            DcmError::Settings(_) => {
+                "Correct the settings file named above; `/dcm validate` lists every problem."
+            }
+            DcmError::Usage(_) => "Run `/dcm help` for the available commands.",
+            DcmError::ExecutableNotFound => {
+                "Install DCM and make sure `dcm` is on PATH, or set `dcm.executable_path`."
+            }
+            DcmError::ExecutableMissing(_) => {
+                "Point `dcm.executable_path` at the DCM executable, or unset it to use `dcm` from PATH."
+            }
+            DcmError::Untrusted(_) => {
+                "Review the paths, then run `/dcm trust approve` and restart the server."
+            }
+            DcmError::Sdk(_) => {
+                "Point `dcm.dart_sdk_path` at the root of a Dart or Flutter SDK, or unset it."
+            }
+            DcmError::Filesystem { .. } => {
+                "Check that the path exists and that its permissions allow the change."
+            }
+            DcmError::Process { .. } => {
+                "Check that the program exists and is executable, and that `extension.toml` grants `process:exec`."
+            }
+            DcmError::CliOutput { .. } => {
+                "Run `/dcm doctor` to check the installed DCM version and license."
+            }
+        }
+    }
+}
+
+impl fmt::Display for DcmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}\nFix: {}",
+            self.code(),
+            self.message(),
+            self.hint()
+        )
+    }
+}
+
+impl std::error::Error for DcmError {}
+
+/// Zed takes errors as strings.
+impl From<DcmError> for String {
+    fn from(err: DcmError) -> Self {
+        err.to_string()
+    }
+}
@@ -0,0 +1,90 @@
+//! Rotating snapshots of the project settings file taken before slash commands edit it.
+
+use std::cmp::Reverse;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::DcmError;
+use crate::host::Host;
+use crate::jsonc;
+use crate::utils::{format_age, unix_timestamp};
+
+/// Directory holding the snapshots, relative to the worktree root.
+pub const HISTORY_DIR: &str = ".zed/dcm-settings-history";
+
+/// Number of snapshots kept; older ones are deleted when a new one is recorded.
+const MAX_ENTRIES: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The change that was about to be applied when the snapshot was taken.
+    pub change: String,
+    /// The `dcm` block before the change, or `None` if there was none.
+    pub dcm: Option<Map<String, Value>>,
+    /// The whole settings file before the change, kept as a last-resort backup.
+    pub file: String,
+}
+
+impl HistoryEntry {
+    pub fn age(&self) -> String {
+        format_age(self.timestamp)
+    }
+}
+
+/// Saves `contents` (the settings file before `change`) as a new snapshot.
+pub fn record(host: &dyn Host, change: &str, contents: &str) -> Result<(), DcmError> {
+    let entries = list(host)?;
+    let id = entries.first().map(|entry| entry.id + 1).unwrap_or(1);
+    let dcm = match jsonc::parse(contents) {
+        Ok(Value::Object(mut root)) => match root.remove("dcm") {
+            Some(Value::Object(block)) => Some(block),
+            _ => None,
+        },
+        _ => None,
+    };
+    let entry = HistoryEntry {
+        id,
+        timestamp: unix_timestamp(),
+        change: change.to_string(),
+        dcm,
+        file: contents.to_string(),
+    };
+
+    let serialized = serde_json::to_string_pretty(&entry).map_err(|err| {
+        DcmError::settings(format!("Failed to serialize settings history: {err}"))
+    })?;
+    host.write_file(&entry_path(host, id), &serialized)?;
+
+    for stale in entries.iter().skip(MAX_ENTRIES - 1) {
+        remove(host, stale.id)?;
+    }
+    Ok(())
+}
+
+/// Returns the snapshots, newest first. Unreadable files are skipped.
+pub fn list(host: &dyn Host) -> Result<Vec<HistoryEntry>, DcmError> {
+    let mut entries: Vec<HistoryEntry> = host
+        .read_dir(&host.root_path().join(HISTORY_DIR))
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| host.read_file(&path).ok())
+        .filter_map(|contents| serde_json::from_str(&contents).ok())
+        .collect();
+    entries.sort_by_key(|entry| Reverse(entry.id));
+    Ok(entries)
+}
+
+pub fn remove(host: &dyn Host, id: u64) -> Result<(), DcmError> {
+    host.remove_file(&entry_path(host, id))
+}
+
+fn entry_path(host: &dyn Host, id: u64) -> PathBuf {
+    host.root_path()
+        .join(HISTORY_DIR)
+        .join(format!("{id:06}.json"))
+}
@@ -0,0 +1,509 @@
+//! Everything the settings and slash command code needs from its surroundings.
+//!
+//! [`WorktreeHost`] forwards to the Zed worktree, `std::fs` and
+//! `zed::process`. Slash command completion is not given a worktree and uses
+//! [`Disk`] on its own. Tests use [`fake::FakeHost`], which keeps files,
+//! programs on PATH and process results in memory, so the code above runs
+//! natively.
+
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use zed_extension_api::process::Output;
+use zed_extension_api::{self as zed, Architecture, Command, EnvVars, Os, Worktree};
+
+use crate::error::DcmError;
+
+/// What is known about an existing file or folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub is_dir: bool,
+    pub len: u64,
+    /// Last modification, in seconds since the Unix epoch.
+    pub modified: u64,
+    pub readonly: bool,
+}
+
+pub trait FileSystem {
+    fn read_file(&self, path: &Path) -> Result<String, DcmError>;
+    /// Replaces `path` with `contents`, creating missing parent folders.
+    fn write_file(&self, path: &Path, contents: &str) -> Result<(), DcmError>;
+    /// Adds `contents` to the end of `path`, creating it and its parent folders.
+    fn append_file(&self, path: &Path, contents: &str) -> Result<(), DcmError>;
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), DcmError>;
+    fn remove_file(&self, path: &Path) -> Result<(), DcmError>;
+    /// Creates `path` and any missing parent folders.
+    fn create_dir(&self, path: &Path) -> Result<(), DcmError>;
+    /// Paths of the entries in `path`, or none when it cannot be read.
+    fn read_dir(&self, path: &Path) -> Vec<PathBuf>;
+    /// `None` when nothing exists at `path`.
+    fn metadata(&self, path: &Path) -> Option<Metadata>;
+    /// The bytes of `path` from `offset` to its end.
+    fn read_from(&self, path: &Path, offset: u64) -> Result<Vec<u8>, DcmError>;
+    /// `path` with symbolic links and `..` resolved, or `None` when it does not exist.
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf>;
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).is_some_and(|metadata| !metadata.is_dir)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).is_some_and(|metadata| metadata.is_dir)
+    }
+}
+
+pub trait Host: FileSystem {
+    /// Identifies the worktree across callbacks.
+    fn id(&self) -> u64;
+    fn root_path(&self) -> PathBuf;
+    /// Reads a file relative to the worktree root.
+    fn read_text_file(&self, path: &str) -> Result<String, String>;
+    fn which(&self, binary_name: &str) -> Option<String>;
+    fn shell_env(&self) -> EnvVars;
+    /// The operating system and architecture Zed runs on.
+    fn platform(&self) -> (Os, Architecture);
+    /// Runs `command` to completion.
+    fn run(&self, command: &Command) -> Result<Output, DcmError>;
+}
+
+/// The local filesystem.
+pub struct Disk;
+
+impl FileSystem for Disk {
+    fn read_file(&self, path: &Path) -> Result<String, DcmError> {
+        fs::read_to_string(path).map_err(|err| DcmError::filesystem("read", path, err))
+    }
+
+    fn write_file(&self, path: &Path, contents: &str) -> Result<(), DcmError> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        write_atomically(path, contents)
+    }
+
+    fn append_file(&self, path: &Path, contents: &str) -> Result<(), DcmError> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|err| DcmError::filesystem("write", path, err))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), DcmError> {
+        fs::rename(from, to).map_err(|err| DcmError::filesystem("rename", from, err))
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), DcmError> {
+        fs::remove_file(path).map_err(|err| DcmError::filesystem("remove", path, err))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), DcmError> {
+        fs::create_dir_all(path).map_err(|err| DcmError::filesystem("create", path, err))
+    }
+
+    fn read_dir(&self, path: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(path) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> Option<Metadata> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Some(Metadata {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified,
+            readonly: metadata.permissions().readonly(),
+        })
+    }
+
+    fn read_from(&self, path: &Path, offset: u64) -> Result<Vec<u8>, DcmError> {
+        let mut bytes = Vec::new();
+        fs::File::open(path)
+            .and_then(|mut file| {
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_to_end(&mut bytes)
+            })
+            .map_err(|err| DcmError::filesystem("read", path, err))?;
+        Ok(bytes)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+        fs::canonicalize(path).ok()
+    }
+}
+
+/// Writes `contents` to a sibling temporary file and renames it over `path`.
+fn write_atomically(path: &Path, contents: &str) -> Result<(), DcmError> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{file_name}.dcm-tmp"));
+
+    fs::write(&temp_path, contents)
+        .map_err(|err| DcmError::filesystem("write", &temp_path, err))?;
+    fs::rename(&temp_path, path).map_err(|err| {
+        let _ = fs::remove_file(&temp_path);
+        DcmError::filesystem("replace", path, err)
+    })
+}
+
+/// The host inside Zed.
+pub struct WorktreeHost<'a> {
+    worktree: &'a Worktree,
+}
+
+impl<'a> WorktreeHost<'a> {
+    pub fn new(worktree: &'a Worktree) -> Self {
+        Self { worktree }
+    }
+}
+
+impl FileSystem for WorktreeHost<'_> {
+    fn read_file(&self, path: &Path) -> Result<String, DcmError> {
+        Disk.read_file(path)
+    }
+
+    fn write_file(&self, path: &Path, contents: &str) -> Result<(), DcmError> {
+        Disk.write_file(path, contents)
+    }
+
+    fn append_file(&self, path: &Path, contents: &str) -> Result<(), DcmError> {
+        Disk.append_file(path, contents)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), DcmError> {
+        Disk.rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), DcmError> {
+        Disk.remove_file(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), DcmError> {
+        Disk.create_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> Vec<PathBuf> {
+        Disk.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Option<Metadata> {
+        Disk.metadata(path)
+    }
+
+    fn read_from(&self, path: &Path, offset: u64) -> Result<Vec<u8>, DcmError> {
+        Disk.read_from(path, offset)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+        Disk.canonicalize(path)
+    }
+}
+
+impl Host for WorktreeHost<'_> {
+    fn id(&self) -> u64 {
+        self.worktree.id()
+    }
+
+    fn root_path(&self) -> PathBuf {
+        PathBuf::from(self.worktree.root_path())
+    }
+
+    fn read_text_file(&self, path: &str) -> Result<String, String> {
+        self.worktree.read_text_file(path)
+    }
+
+    fn which(&self, binary_name: &str) -> Option<String> {
+        self.worktree.which(binary_name)
+    }
+
+    fn shell_env(&self) -> EnvVars {
+        self.worktree.shell_env()
+    }
+
+    fn platform(&self) -> (Os, Architecture) {
+        zed::current_platform()
+    }
+
+    fn run(&self, command: &Command) -> Result<Output, DcmError> {
+        let mut command = Command {
+            command: command.command.clone(),
+            args: command.args.clone(),
+            env: command.env.clone(),
+        };
+        command
+            .output()
+            .map_err(|err| DcmError::process(&command.command, err))
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::cell::{Cell, RefCell};
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    use super::*;
+
+    struct FakeFile {
+        contents: String,
+        modified: u64,
+    }
+
+    /// An in-memory worktree with its own filesystem, PATH and processes.
+    pub struct FakeHost {
+        root: PathBuf,
+        env: EnvVars,
+        files: RefCell<BTreeMap<PathBuf, FakeFile>>,
+        dirs: RefCell<BTreeSet<PathBuf>>,
+        readonly: BTreeSet<PathBuf>,
+        /// Modification time given to the next write; every write advances it.
+        clock: Cell<u64>,
+        os: Os,
+        binaries: HashMap<String, String>,
+        outputs: HashMap<Vec<String>, Output>,
+        /// Every command run, as program followed by arguments.
+        pub commands: RefCell<Vec<Vec<String>>>,
+    }
+
+    impl FakeHost {
+        /// A worktree at `/work/app` for a user whose home is `/home/dev`.
+        pub fn new() -> Self {
+            Self {
+                root: PathBuf::from("/work/app"),
+                env: vec![
+                    ("HOME".to_string(), "/home/dev".to_string()),
+                    ("PATH".to_string(), "/usr/local/bin:/usr/bin".to_string()),
+                ],
+                files: RefCell::default(),
+                dirs: RefCell::default(),
+                readonly: BTreeSet::new(),
+                clock: Cell::new(1),
+                os: Os::Linux,
+                binaries: HashMap::new(),
+                outputs: HashMap::new(),
+                commands: RefCell::default(),
+            }
+        }
+
+        /// Adds a file; relative paths are inside the worktree.
+        pub fn with_file(self, path: &str, contents: &str) -> Self {
+            self.write_file(&self.root.join(path), contents).unwrap();
+            self
+        }
+
+        /// Adds an empty folder; relative paths are inside the worktree.
+        pub fn with_dir(self, path: &str) -> Self {
+            self.dirs.borrow_mut().insert(self.root.join(path));
+            self
+        }
+
+        /// Pretends Zed runs on `os` instead of Linux.
+        pub fn with_os(mut self, os: Os) -> Self {
+            self.os = os;
+            self
+        }
+
+        /// Marks a file or folder as not writable.
+        pub fn with_readonly(mut self, path: &str) -> Self {
+            self.readonly.insert(self.root.join(path));
+            self
+        }
+
+        /// Puts an executable file on PATH under `name`.
+        pub fn with_binary(mut self, name: &str, path: &str) -> Self {
+            self.binaries.insert(name.to_string(), path.to_string());
+            self.with_file(path, "")
+        }
+
+        pub fn with_env(mut self, key: &str, value: &str) -> Self {
+            self.env.retain(|(name, _)| name != key);
+            self.env.push((key.to_string(), value.to_string()));
+            self
+        }
+
+        /// Makes `argv` exit with `status` after printing `stdout`.
+        pub fn with_output(mut self, argv: &[&str], status: i32, stdout: &str) -> Self {
+            let output = Output {
+                status: Some(status),
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: Vec::new(),
+            };
+            self.outputs
+                .insert(argv.iter().map(|arg| arg.to_string()).collect(), output);
+            self
+        }
+
+        /// Contents of a file; relative paths are inside the worktree.
+        pub fn file(&self, path: &str) -> Option<String> {
+            self.files
+                .borrow()
+                .get(&self.root.join(path))
+                .map(|file| file.contents.clone())
+        }
+
+        fn tick(&self) -> u64 {
+            let now = self.clock.get();
+            self.clock.set(now + 1);
+            now
+        }
+    }
+
+    impl FileSystem for FakeHost {
+        fn read_file(&self, path: &Path) -> Result<String, DcmError> {
+            self.files
+                .borrow()
+                .get(path)
+                .map(|file| file.contents.clone())
+                .ok_or_else(|| DcmError::filesystem("read", path, "no such file"))
+        }
+
+        fn write_file(&self, path: &Path, contents: &str) -> Result<(), DcmError> {
+            let modified = self.tick();
+            self.files.borrow_mut().insert(
+                path.to_path_buf(),
+                FakeFile {
+                    contents: contents.to_string(),
+                    modified,
+                },
+            );
+            Ok(())
+        }
+
+        fn append_file(&self, path: &Path, contents: &str) -> Result<(), DcmError> {
+            let previous = self.read_file(path).unwrap_or_default();
+            self.write_file(path, &(previous + contents))
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<(), DcmError> {
+            let file = self
+                .files
+                .borrow_mut()
+                .remove(from)
+                .ok_or_else(|| DcmError::filesystem("rename", from, "no such file"))?;
+            self.files.borrow_mut().insert(to.to_path_buf(), file);
+            Ok(())
+        }
+
+        fn remove_file(&self, path: &Path) -> Result<(), DcmError> {
+            self.files
+                .borrow_mut()
+                .remove(path)
+                .map(|_| ())
+                .ok_or_else(|| DcmError::filesystem("remove", path, "no such file"))
+        }
+
+        fn create_dir(&self, path: &Path) -> Result<(), DcmError> {
+            self.dirs.borrow_mut().insert(path.to_path_buf());
+            Ok(())
+        }
+
+        fn read_dir(&self, path: &Path) -> Vec<PathBuf> {
+            let files = self.files.borrow();
+            let dirs = self.dirs.borrow();
+            let entries: BTreeSet<PathBuf> = files
+                .keys()
+                .chain(dirs.iter())
+                .filter_map(|entry| {
+                    let rest = entry.strip_prefix(path).ok()?;
+                    let first = rest.components().next()?;
+                    Some(path.join(first))
+                })
+                .collect();
+            entries.into_iter().collect()
+        }
+
+        fn read_from(&self, path: &Path, offset: u64) -> Result<Vec<u8>, DcmError> {
+            let contents = self.read_file(path)?;
+            let start = usize::try_from(offset).unwrap_or(usize::MAX);
+            Ok(contents
+                .as_bytes()
+                .get(start..)
+                .unwrap_or_default()
+                .to_vec())
+        }
+
+        fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+            self.metadata(path).map(|_| path.to_path_buf())
+        }
+
+        fn metadata(&self, path: &Path) -> Option<Metadata> {
+            let readonly = self.readonly.contains(path);
+            if let Some(file) = self.files.borrow().get(path) {
+                return Some(Metadata {
+                    is_dir: false,
+                    len: file.contents.len() as u64,
+                    modified: file.modified,
+                    readonly,
+                });
+            }
+            let is_dir = self
+                .dirs
+                .borrow()
+                .iter()
+                .chain(self.files.borrow().keys())
+                .any(|entry| entry.starts_with(path));
+            is_dir.then_some(Metadata {
+                is_dir: true,
+                len: 0,
+                modified: 0,
+                readonly,
+            })
+        }
+    }
+
+    impl Host for FakeHost {
+        fn id(&self) -> u64 {
+            1
+        }
+
+        fn root_path(&self) -> PathBuf {
+            self.root.clone()
+        }
+
+        fn read_text_file(&self, path: &str) -> Result<String, String> {
+            self.file(path)
+                .ok_or_else(|| format!("no such file: {path}"))
+        }
+
+        fn which(&self, binary_name: &str) -> Option<String> {
+            self.binaries.get(binary_name).cloned()
+        }
+
+        fn shell_env(&self) -> EnvVars {
+            self.env.clone()
+        }
+
+        fn platform(&self) -> (Os, Architecture) {
+            (self.os, Architecture::X8664)
+        }
+
+        fn run(&self, command: &Command) -> Result<Output, DcmError> {
+            let argv: Vec<String> = std::iter::once(command.command.clone())
+                .chain(command.args.iter().cloned())
+                .collect();
+            self.commands.borrow_mut().push(argv.clone());
+            self.outputs
+                .get(&argv)
+                .cloned()
+                .ok_or_else(|| DcmError::process(&command.command, "not found"))
+        }
+    }
+}
@@ -0,0 +1,684 @@
+//! Minimal JSONC support for Zed settings files.
+//!
+//! Zed settings allow `//` and `/* */` comments as well as trailing commas.
+//! [`parse`] accepts that dialect, and [`set_value`] rewrites a single key in
+//! place so the rest of the file keeps its comments, ordering and formatting.
+
+use serde_json::Value;
+
+/// Parses JSONC text into a [`Value`].
+pub fn parse(text: &str) -> Result<Value, String> {
+    let stripped = strip_trailing_commas(&strip_comments(text));
+    serde_json::from_str(&stripped).map_err(|err| err.to_string())
+}
+
+/// Sets (or, with `None`, removes) the value at `path`, returning the edited text.
+///
+/// Only the bytes belonging to the affected member are touched. Missing
+/// intermediate objects are created; an empty document becomes a new object.
+pub fn set_value(text: &str, path: &[&str], value: Option<&Value>) -> Result<String, String> {
+    if path.is_empty() {
+        return Err("settings path is empty".to_string());
+    }
+
+    if text.trim().is_empty() {
+        return match value {
+            Some(value) => {
+                let rendered = render(&nest(&path[1..], value));
+                Ok(format!(
+                    "{{\n  {}: {}\n}}\n",
+                    quote(path[0]),
+                    indent_tail(&rendered, "  ")
+                ))
+            }
+            None => Ok(text.to_string()),
+        };
+    }
+
+    let mut scanner = Scanner::new(text);
+    scanner.skip_trivia()?;
+    if scanner.peek() != Some(b'{') {
+        return Err("settings file must contain a JSON object".to_string());
+    }
+    let mut object = scanner.object_at(scanner.pos)?;
+
+    for (index, segment) in path.iter().enumerate() {
+        let is_last = index + 1 == path.len();
+        // `parse` keeps the last of duplicate keys, so that is the one to edit.
+        let member = object.members.iter().rposition(|m| m.key == *segment);
+
+        match (member, value) {
+            (Some(position), _) if !is_last => {
+                let member = &object.members[position];
+                if text.as_bytes()[member.value_start] == b'{' {
+                    object = Scanner::new(text).object_at(member.value_start)?;
+                    continue;
+                }
+                let Some(value) = value else {
+                    return Ok(text.to_string());
+                };
+                let replacement = nest(&path[index + 1..], value);
+                let indent = line_indent(text, member.key_start);
+                return Ok(splice(
+                    text,
+                    member.value_start,
+                    member.value_end,
+                    &indent_tail(&render(&replacement), &indent),
+                ));
+            }
+            (Some(position), Some(value)) => {
+                let member = &object.members[position];
+                let indent = line_indent(text, member.key_start);
+                return Ok(splice(
+                    text,
+                    member.value_start,
+                    member.value_end,
+                    &indent_tail(&render(value), &indent),
+                ));
+            }
+            (Some(position), None) => return Ok(remove_member(text, &object, position)),
+            (None, None) => return Ok(text.to_string()),
+            (None, Some(value)) => {
+                let nested = nest(&path[index + 1..], value);
+                return Ok(insert_member(text, &object, segment, &nested));
+            }
+        }
+    }
+
+    Ok(text.to_string())
+}
+
+struct Member {
+    key: String,
+    key_start: usize,
+    value_start: usize,
+    value_end: usize,
+    comma: Option<usize>,
+}
+
+struct ObjectSpan {
+    open: usize,
+    close: usize,
+    members: Vec<Member>,
+}
+
+struct Scanner<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            bytes: text.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.pos.min(self.text.len())]
+            .matches('\n')
+            .count()
+            + 1;
+        format!("{message} at line {line}")
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r' | b'\n') => self.pos += 1,
+                Some(b'/') if self.bytes.get(self.pos + 1) == Some(&b'/') => {
+                    while let Some(byte) = self.peek() {
+                        if byte == b'\n' {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                Some(b'/') if self.bytes.get(self.pos + 1) == Some(&b'*') => {
+                    match self.text[self.pos + 2..].find("*/") {
+                        Some(end) => self.pos += end + 4,
+                        None => return Err(self.error("unterminated block comment")),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn object_at(&mut self, open: usize) -> Result<ObjectSpan, String> {
+        self.pos = open + 1;
+        let mut members = Vec::new();
+
+        loop {
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b'}') => {
+                    return Ok(ObjectSpan {
+                        open,
+                        close: self.pos,
+                        members,
+                    })
+                }
+                Some(b'"') => {}
+                _ => return Err(self.error("expected object key")),
+            }
+
+            let key_start = self.pos;
+            self.skip_string()?;
+            let key: String = serde_json::from_str(&self.text[key_start..self.pos])
+                .map_err(|err| self.error(&format!("invalid object key: {err}")))?;
+
+            self.skip_trivia()?;
+            if self.peek() != Some(b':') {
+                return Err(self.error("expected `:`"));
+            }
+            self.pos += 1;
+            self.skip_trivia()?;
+
+            let value_start = self.pos;
+            self.skip_value()?;
+            let value_end = self.pos;
+
+            self.skip_trivia()?;
+            let comma = if self.peek() == Some(b',') {
+                self.pos += 1;
+                Some(self.pos - 1)
+            } else {
+                None
+            };
+
+            members.push(Member {
+                key,
+                key_start,
+                value_start,
+                value_end,
+                comma,
+            });
+
+            if comma.is_none() {
+                self.skip_trivia()?;
+                if self.peek() != Some(b'}') {
+                    return Err(self.error("expected `,` or `}`"));
+                }
+            }
+        }
+    }
+
+    fn skip_string(&mut self) -> Result<(), String> {
+        self.pos += 1;
+        while let Some(byte) = self.peek() {
+            self.pos += 1;
+            match byte {
+                b'\\' => self.pos += 1,
+                b'"' => return Ok(()),
+                _ => {}
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn skip_value(&mut self) -> Result<(), String> {
+        match self.peek() {
+            Some(b'{') => {
+                let object = self.object_at(self.pos)?;
+                self.pos = object.close + 1;
+                Ok(())
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                loop {
+                    self.skip_trivia()?;
+                    if self.peek() == Some(b']') {
+                        self.pos += 1;
+                        return Ok(());
+                    }
+                    self.skip_value()?;
+                    self.skip_trivia()?;
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {}
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some(b'"') => self.skip_string(),
+            Some(_) => {
+                let start = self.pos;
+                while let Some(byte) = self.peek() {
+                    if matches!(
+                        byte,
+                        b',' | b'}' | b']' | b'/' | b' ' | b'\t' | b'\r' | b'\n'
+                    ) {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    Err(self.error("expected value"))
+                } else {
+                    Ok(())
+                }
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+}
+
+/// Replaces comments with spaces, keeping newlines so error positions stay accurate.
+fn strip_comments(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    let mut in_string = false;
+
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if in_string {
+            out.push(byte);
+            if byte == b'\\' && index + 1 < bytes.len() {
+                out.push(bytes[index + 1]);
+                index += 1;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            index += 1;
+            continue;
+        }
+
+        match (byte, bytes.get(index + 1)) {
+            (b'"', _) => {
+                in_string = true;
+                out.push(byte);
+                index += 1;
+            }
+            (b'/', Some(b'/')) => {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    out.push(b' ');
+                    index += 1;
+                }
+            }
+            (b'/', Some(b'*')) => {
+                out.extend_from_slice(b"  ");
+                index += 2;
+                while index < bytes.len()
+                    && !(bytes[index] == b'*' && bytes.get(index + 1) == Some(&b'/'))
+                {
+                    out.push(if bytes[index] == b'\n' { b'\n' } else { b' ' });
+                    index += 1;
+                }
+                if index < bytes.len() {
+                    out.extend_from_slice(b"  ");
+                    index += 2;
+                }
+            }
+            _ => {
+                out.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8(out)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
+
+/// Blanks out commas that directly precede a closing `}` or `]`.
+fn strip_trailing_commas(text: &str) -> String {
+    let mut bytes = text.as_bytes().to_vec();
+    let mut in_string = false;
+    let mut pending_comma: Option<usize> = None;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if in_string {
+            if byte == b'\\' {
+                index += 1;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            index += 1;
+            continue;
+        }
+
+        match byte {
+            b'"' => {
+                in_string = true;
+                pending_comma = None;
+            }
+            b',' => pending_comma = Some(index),
+            b'}' | b']' => {
+                if let Some(comma) = pending_comma.take() {
+                    bytes[comma] = b' ';
+                }
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => {}
+            _ => pending_comma = None,
+        }
+        index += 1;
+    }
+
+    String::from_utf8(bytes)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
+
+fn insert_member(text: &str, object: &ObjectSpan, key: &str, value: &Value) -> String {
+    let outer_indent = line_indent(text, object.open);
+    match object.members.last() {
+        Some(last) => {
+            let indent = line_indent(text, last.key_start);
+            let entry = format!("{}: {}", quote(key), indent_tail(&render(value), &indent));
+            let after = last.comma.map_or(last.value_end, |comma| comma + 1);
+            match text[after..].find('\n') {
+                // Start a new line after the last member's own `// comment`,
+                // so the comment stays with it.
+                Some(offset) if is_blank_or_line_comment(&text[after..after + offset]) => {
+                    let line_end = after + text[after..after + offset].trim_end_matches('\r').len();
+                    let text = splice(text, line_end, line_end, &format!("\n{indent}{entry}"));
+                    match last.comma {
+                        Some(_) => text,
+                        None => splice(&text, last.value_end, last.value_end, ","),
+                    }
+                }
+                _ => splice(
+                    text,
+                    last.value_end,
+                    last.value_end,
+                    &format!(",\n{indent}{entry}"),
+                ),
+            }
+        }
+        None => {
+            let indent = format!("{outer_indent}  ");
+            // Comments are the only thing an empty object can hold; keep them
+            // above the new member.
+            let inner = text[object.open + 1..object.close].trim_end();
+            let entry = format!(
+                "{inner}\n{indent}{}: {}\n{outer_indent}",
+                quote(key),
+                indent_tail(&render(value), &indent)
+            );
+            splice(text, object.open + 1, object.close, &entry)
+        }
+    }
+}
+
+fn remove_member(text: &str, object: &ObjectSpan, position: usize) -> String {
+    let member = &object.members[position];
+
+    if let Some(comma) = member.comma {
+        let start = line_start_if_blank(text, member.key_start);
+        let end = line_end_if_blank(text, comma + 1);
+        return splice(text, start, end, "");
+    }
+
+    match position.checked_sub(1).map(|prev| &object.members[prev]) {
+        // Drop the previous member's comma, but keep anything after it on
+        // that line and any comment lines above the removed member.
+        Some(Member {
+            comma: Some(comma), ..
+        }) => {
+            let line_start = line_start_if_blank(text, member.key_start);
+            let (start, end) =
+                if line_start < member.key_start || text[..line_start].ends_with('\n') {
+                    (line_start, line_end_if_blank(text, member.value_end))
+                } else {
+                    let start = text[..member.key_start].trim_end().len().max(comma + 1);
+                    (start, member.value_end)
+                };
+            let text = splice(text, start, end, "");
+            splice(&text, *comma, comma + 1, "")
+        }
+        Some(previous) => splice(text, previous.value_end, member.value_end, ""),
+        None if text[object.open + 1..member.key_start].trim().is_empty()
+            && text[member.value_end..object.close].trim().is_empty() =>
+        {
+            splice(text, object.open + 1, object.close, "")
+        }
+        // Keep the comments around the only member.
+        None => splice(
+            text,
+            line_start_if_blank(text, member.key_start),
+            line_end_if_blank(text, member.value_end),
+            "",
+        ),
+    }
+}
+
+fn is_blank_or_line_comment(text: &str) -> bool {
+    let text = text.trim();
+    text.is_empty() || text.starts_with("//")
+}
+
+/// Returns the start of the line containing `pos` when only whitespace precedes it.
+fn line_start_if_blank(text: &str, pos: usize) -> usize {
+    let line_start = text[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    if text[line_start..pos].trim().is_empty() {
+        line_start
+    } else {
+        pos
+    }
+}
+
+/// Extends `pos` past the newline when only whitespace or a line comment follows it.
+fn line_end_if_blank(text: &str, pos: usize) -> usize {
+    match text[pos..].find('\n') {
+        Some(offset) => {
+            let rest = text[pos..pos + offset].trim();
+            if rest.is_empty() || rest.starts_with("//") {
+                pos + offset + 1
+            } else {
+                pos
+            }
+        }
+        None => pos,
+    }
+}
+
+fn line_indent(text: &str, pos: usize) -> String {
+    let line_start = text[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    text[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+fn splice(text: &str, start: usize, end: usize, replacement: &str) -> String {
+    let mut out = String::with_capacity(text.len() + replacement.len());
+    out.push_str(&text[..start]);
+    out.push_str(replacement);
+    out.push_str(&text[end..]);
+    out
+}
+
+fn nest(path: &[&str], value: &Value) -> Value {
+    path.iter().rev().fold(value.clone(), |inner, key| {
+        let mut map = serde_json::Map::new();
+        map.insert((*key).to_string(), inner);
+        Value::Object(map)
+    })
+}
+
+fn render(value: &Value) -> String {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Indents every line after the first so nested values line up with `indent`.
+fn indent_tail(rendered: &str, indent: &str) -> String {
+    rendered.replace('\n', &format!("\n{indent}"))
+}
+
+fn quote(key: &str) -> String {
+    Value::String(key.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_comments_and_trailing_commas() {
+        let text = r#"{
+  // line comment
+  "url": "https://dcm.dev/a//b", /* block */
+  "list": [1, 2,],
+}"#;
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({ "url": "https://dcm.dev/a//b", "list": [1, 2] })
+        );
+    }
+
+    #[test]
+    fn replacing_a_value_keeps_comments_and_trailing_commas() {
+        let text = "{\n  // theme\n  \"theme\": \"One\",\n  \"dcm\": {\n    \"show_unused_code\": false, // why\n  },\n}\n";
+        let edited = set_value(text, &["dcm", "show_unused_code"], Some(&json!(true))).unwrap();
+        assert_eq!(
+            edited,
+            "{\n  // theme\n  \"theme\": \"One\",\n  \"dcm\": {\n    \"show_unused_code\": true, // why\n  },\n}\n"
+        );
+    }
+
+    #[test]
+    fn inserts_into_an_empty_object() {
+        assert_eq!(
+            set_value("{}", &["dcm", "profile"], Some(&json!("ci"))).unwrap(),
+            "{\n  \"dcm\": {\n    \"profile\": \"ci\"\n  }\n}"
+        );
+        assert_eq!(
+            set_value("", &["dcm"], Some(&json!({}))).unwrap(),
+            "{\n  \"dcm\": {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn inserting_into_a_comment_only_object_keeps_the_comment() {
+        let text = "{\n  // Zed settings\n}\n";
+        let edited = set_value(text, &["theme"], Some(&json!("One"))).unwrap();
+        assert_eq!(edited, "{\n  // Zed settings\n  \"theme\": \"One\"\n}\n");
+        assert_eq!(parse(&edited).unwrap(), json!({ "theme": "One" }));
+    }
+
+    #[test]
+    fn inserts_after_the_last_member() {
+        let text = "{\n  \"dcm\": {\n    \"profile\": \"ci\"\n  }\n}";
+        let edited = set_value(text, &["dcm", "debug_log"], Some(&json!(true))).unwrap();
+        assert_eq!(
+            edited,
+            "{\n  \"dcm\": {\n    \"profile\": \"ci\",\n    \"debug_log\": true\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn creates_missing_nested_objects() {
+        let text = "{\n  \"theme\": \"One\"\n}";
+        let edited = set_value(text, &["dcm", "profiles", "ci"], Some(&json!({}))).unwrap();
+        assert_eq!(
+            parse(&edited).unwrap(),
+            json!({ "theme": "One", "dcm": { "profiles": { "ci": {} } } })
+        );
+        assert!(edited.contains("\"dcm\": {\n    \"profiles\": {\n      \"ci\": {}\n    }\n  }"));
+    }
+
+    #[test]
+    fn replaces_a_non_object_on_the_path() {
+        let text = "{ \"dcm\": null }";
+        let edited = set_value(text, &["dcm", "debug_log"], Some(&json!(true))).unwrap();
+        assert_eq!(
+            parse(&edited).unwrap(),
+            json!({ "dcm": { "debug_log": true } })
+        );
+    }
+
+    #[test]
+    fn removes_members() {
+        let text = "{\n  \"a\": 1,\n  \"b\": 2\n}";
+        assert_eq!(set_value(text, &["a"], None).unwrap(), "{\n  \"b\": 2\n}");
+        assert_eq!(set_value(text, &["b"], None).unwrap(), "{\n  \"a\": 1\n}");
+        assert_eq!(set_value(text, &["c"], None).unwrap(), text);
+    }
+
+    #[test]
+    fn removing_the_last_member_keeps_comments() {
+        let text = "{\n  // DCM\n  \"dcm\": { \"debug_log\": true }\n}\n";
+        assert_eq!(
+            set_value(text, &["dcm", "debug_log"], None).unwrap(),
+            "{\n  // DCM\n  \"dcm\": {}\n}\n"
+        );
+        assert_eq!(set_value(text, &["dcm"], None).unwrap(), "{\n  // DCM\n}\n");
+    }
+
+    #[test]
+    fn removing_the_last_member_keeps_the_previous_comment() {
+        let text = "{\"a\": 1, // keep me\n \"b\": 2}";
+        assert_eq!(
+            set_value(text, &["b"], None).unwrap(),
+            "{\"a\": 1 // keep me\n}"
+        );
+
+        let text = "{\n  \"a\": 1, // keep me\n  // about b\n  \"b\": 2 // b\n}\n";
+        let edited = set_value(text, &["b"], None).unwrap();
+        assert_eq!(edited, "{\n  \"a\": 1 // keep me\n  // about b\n}\n");
+        assert_eq!(parse(&edited).unwrap(), json!({ "a": 1 }));
+
+        assert_eq!(
+            set_value(r#"{"a": 1, "b": 2}"#, &["b"], None).unwrap(),
+            r#"{"a": 1}"#
+        );
+    }
+
+    #[test]
+    fn inserting_keeps_a_trailing_comment_on_its_member() {
+        let text = "{\n  \"a\": 1 // note on a\n}";
+        let edited = set_value(text, &["b"], Some(&json!(2))).unwrap();
+        assert_eq!(edited, "{\n  \"a\": 1, // note on a\n  \"b\": 2\n}");
+
+        let text = "{\n  \"a\": 1, // note on a\n}";
+        let edited = set_value(text, &["b"], Some(&json!(2))).unwrap();
+        assert_eq!(edited, "{\n  \"a\": 1, // note on a\n  \"b\": 2\n}");
+    }
+
+    #[test]
+    fn edits_the_last_of_duplicate_keys() {
+        let text = r#"{ "a": 1, "a": 2 }"#;
+        let edited = set_value(text, &["a"], Some(&json!(3))).unwrap();
+        assert_eq!(edited, r#"{ "a": 1, "a": 3 }"#);
+        assert_eq!(parse(&edited).unwrap(), json!({ "a": 3 }));
+
+        let text = r#"{ "dcm": {}, "dcm": { "x": 1 } }"#;
+        let edited = set_value(text, &["dcm", "x"], Some(&json!(2))).unwrap();
+        assert_eq!(parse(&edited).unwrap(), json!({ "dcm": { "x": 2 } }));
+    }
+
+    #[test]
+    fn handles_escaped_strings() {
+        let text = r#"{ "say \"hi\"": "a \\ b // not a comment", "dcm": {} }"#;
+        let edited = set_value(text, &["dcm", "x"], Some(&json!("tab\there"))).unwrap();
+        assert_eq!(
+            parse(&edited).unwrap(),
+            json!({ "say \"hi\"": "a \\ b // not a comment", "dcm": { "x": "tab\there" } })
+        );
+        assert!(edited.contains(r#""x": "tab\there""#));
+    }
+
+    #[test]
+    fn rejects_non_objects() {
+        assert!(set_value("[]", &["dcm"], Some(&json!(1))).is_err());
+        assert!(set_value("{ \"a\" 1 }", &["dcm"], Some(&json!(1))).is_err());
+        assert!(set_value("{}", &[], Some(&json!(1))).is_err());
+    }
+}
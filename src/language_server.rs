@@ -1,56 +1,32 @@
-use std::path::PathBuf;
-
-use serde_json::json;
+use serde_json::{json, Map, Value};
 use zed_extension_api::{Command, EnvVars};
 
 use crate::config::ResolvedSettings;
+use crate::error::DcmError;
+use crate::schema::{CliFlag, SETTINGS};
 use crate::utils::path_to_string;
 
-pub fn build_command(settings: &ResolvedSettings) -> Result<Command, String> {
+pub fn build_command(settings: &ResolvedSettings) -> Result<Command, DcmError> {
     let executable = path_to_string(&settings.executable_path)?;
     let mut args = Vec::new();
     args.push("start-server".to_string());
-
-    if let Some(sdk_path) = &settings.sdk_path {
-        args.push(format!("--sdk-path={}", path_to_string(sdk_path)?));
-    }
-
     args.push(format!(
         "--root-folder={}",
         path_to_string(&settings.root_path)?
     ));
 
-    if !settings.user.show_new_version {
-        args.push("--no-show-new-version-update".to_string());
-    }
-
-    if settings.user.show_unused_code {
-        args.push("--show-unused-code".to_string());
-    }
-
-    if settings.user.analyze_only_opened {
-        args.push("--only-opened".to_string());
-    }
-
-    if !settings.excluded_folders.is_empty() {
-        let joined = join_paths(&settings.excluded_folders)?;
-        args.push(format!("--excluded-folders={joined}"));
-    }
-
-    if settings.user.show_unused_files {
-        args.push("--show-unused-files".to_string());
-    }
-
-    if settings.user.disable_baseline {
-        args.push("--disable-baseline".to_string());
-    }
-
-    if let Some(log_path) = &settings.log_file_path {
-        args.push(format!("--logs={}", path_to_string(log_path)?));
-    }
-
-    if settings.user.enable_old_formatter {
-        args.push("--old-formatter".to_string());
+    for spec in SETTINGS {
+        let value = (spec.resolve)(settings)?;
+        match (spec.flag, &value) {
+            (CliFlag::IfTrue(flag), Value::Bool(true)) => args.push(flag.to_string()),
+            (CliFlag::IfFalse(flag), Value::Bool(false)) => args.push(flag.to_string()),
+            (CliFlag::Value(flag), Value::String(value)) => args.push(format!("{flag}={value}")),
+            (CliFlag::Value(flag), Value::Array(items)) if !items.is_empty() => {
+                let joined: Vec<&str> = items.iter().filter_map(Value::as_str).collect();
+                args.push(format!("{flag}={}", joined.join(",")));
+            }
+            _ => {}
+        }
     }
 
     args.push("--client=zed".to_string());
@@ -62,62 +38,43 @@ pub fn build_command(settings: &ResolvedSettings) -> Result<Command, String> {
     Ok(command)
 }
 
-pub fn initialization_options(settings: &ResolvedSettings) -> serde_json::Value {
-    json!({
-        "show_unused_code": settings.user.show_unused_code,
-        "show_unused_files": settings.user.show_unused_files,
-        "disable_baseline": settings.user.disable_baseline,
-        "enable_old_formatter": settings.user.enable_old_formatter,
-        "analyze_only_opened": settings.user.analyze_only_opened,
-        "log_file_path": settings
-            .log_file_path
-            .as_ref()
-            .and_then(|path| path_to_string(path).ok()),
-    })
+pub fn initialization_options(settings: &ResolvedSettings) -> Result<Value, DcmError> {
+    let mut options = Map::new();
+    for spec in SETTINGS.iter().filter(|spec| spec.sent_to_server) {
+        options.insert(spec.key.to_string(), (spec.resolve)(settings)?);
+    }
+    Ok(Value::Object(options))
 }
 
-pub fn workspace_configuration(settings: &ResolvedSettings) -> Result<serde_json::Value, String> {
-    let mut excluded = Vec::with_capacity(settings.excluded_folders.len());
-    for path in &settings.excluded_folders {
-        excluded.push(path_to_string(path)?);
+pub fn workspace_configuration(settings: &ResolvedSettings) -> Result<Value, DcmError> {
+    let mut config = Map::new();
+    for spec in SETTINGS.iter().filter(|spec| spec.sent_to_server) {
+        config.insert(spec.camel_key.to_string(), (spec.resolve)(settings)?);
     }
-
-    let config = json!({
-        "dcm": {
-            "showUnusedCode": settings.user.show_unused_code,
-            "showUnusedFiles": settings.user.show_unused_files,
-            "disableBaseline": settings.user.disable_baseline,
-            "enableOldFormatter": settings.user.enable_old_formatter,
-            "analyzeOnlyOpened": settings.user.analyze_only_opened,
-            "logFilePath": settings
-                .log_file_path
-                .as_ref()
-                .and_then(|path| path_to_string(path).ok()),
-            "dartSdkPath": settings
-                .sdk_path
-                .as_ref()
-                .and_then(|path| path_to_string(path).ok()),
-            "excludedFolders": excluded,
-        }
-    });
-
-    Ok(config)
+    Ok(json!({ "dcm": config }))
 }
 
-fn build_environment(settings: &ResolvedSettings) -> Result<EnvVars, String> {
+pub fn build_environment(settings: &ResolvedSettings) -> Result<EnvVars, DcmError> {
     let mut env = settings.env.clone();
-    upsert_env(&mut env, "PWD", path_to_string(&settings.root_path)?);
-    upsert_env(
-        &mut env,
-        "ZED_WORKTREE_ROOT",
-        path_to_string(&settings.root_path)?,
-    );
+    for (key, value) in environment_overrides(settings)? {
+        upsert_env(&mut env, &key, value);
+    }
+    Ok(env)
+}
+
+/// Returns the variables the extension sets on top of the worktree shell environment.
+pub fn environment_overrides(settings: &ResolvedSettings) -> Result<EnvVars, DcmError> {
+    let root = path_to_string(&settings.root_path)?;
+    let mut overrides = vec![
+        ("PWD".to_string(), root.clone()),
+        ("ZED_WORKTREE_ROOT".to_string(), root),
+    ];
 
     if let Some(sdk_path) = &settings.sdk_path {
-        upsert_env(&mut env, "DART_SDK", path_to_string(sdk_path)?);
+        overrides.push(("DART_SDK".to_string(), path_to_string(sdk_path)?));
     }
 
-    Ok(env)
+    Ok(overrides)
 }
 
 fn upsert_env(env: &mut EnvVars, key: &str, value: String) {
@@ -128,10 +85,118 @@ fn upsert_env(env: &mut EnvVars, key: &str, value: String) {
     }
 }
 
-fn join_paths(paths: &[PathBuf]) -> Result<String, String> {
-    let mut values = Vec::with_capacity(paths.len());
-    for path in paths {
-        values.push(path_to_string(path)?);
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::{LayeredSettings, UserSettings};
+
+    fn settings(user: UserSettings) -> ResolvedSettings {
+        ResolvedSettings {
+            sdk_path: user.dart_sdk_path.as_ref().map(PathBuf::from),
+            log_file_path: user.log_file_path.as_ref().map(PathBuf::from),
+            log_directory: user.log_directory.as_ref().map(PathBuf::from),
+            excluded_folders: user.excluded_folders.iter().map(PathBuf::from).collect(),
+            layered: LayeredSettings {
+                user,
+                ..LayeredSettings::default()
+            },
+            executable_path: PathBuf::from("/opt/dcm/bin/dcm"),
+            root_path: PathBuf::from("/work/app"),
+            env: vec![("HOME".to_string(), "/home/dev".to_string())],
+        }
+    }
+
+    fn everything_enabled() -> ResolvedSettings {
+        settings(UserSettings {
+            executable_path: Some("/opt/dcm/bin/dcm".to_string()),
+            dart_sdk_path: Some("/opt/dart-sdk".to_string()),
+            show_new_version: false,
+            show_unused_code: true,
+            show_unused_files: true,
+            disable_baseline: true,
+            enable_old_formatter: true,
+            analyze_only_opened: true,
+            excluded_folders: vec!["/work/app/build".to_string(), "/work/app/gen".to_string()],
+            log_file_path: Some("/work/app/.zed/dcm.log".to_string()),
+            ..UserSettings::default()
+        })
+    }
+
+    #[test]
+    fn default_command_golden() {
+        let command = build_command(&settings(UserSettings::default())).unwrap();
+        assert_eq!(command.command, "/opt/dcm/bin/dcm");
+        assert_eq!(
+            command.args,
+            ["start-server", "--root-folder=/work/app", "--client=zed"]
+        );
+    }
+
+    #[test]
+    fn full_command_golden() {
+        let command = build_command(&everything_enabled()).unwrap();
+        assert_eq!(
+            command.args,
+            [
+                "start-server",
+                "--root-folder=/work/app",
+                "--sdk-path=/opt/dart-sdk",
+                "--no-show-new-version-update",
+                "--show-unused-code",
+                "--show-unused-files",
+                "--disable-baseline",
+                "--old-formatter",
+                "--only-opened",
+                "--excluded-folders=/work/app/build,/work/app/gen",
+                "--logs=/work/app/.zed/dcm.log",
+                "--client=zed",
+            ]
+        );
+        assert!(command
+            .env
+            .contains(&("DART_SDK".to_string(), "/opt/dart-sdk".to_string())));
+        assert!(command
+            .env
+            .contains(&("ZED_WORKTREE_ROOT".to_string(), "/work/app".to_string())));
+    }
+
+    #[test]
+    fn initialization_options_golden() {
+        assert_eq!(
+            initialization_options(&everything_enabled()).unwrap(),
+            json!({
+                "dart_sdk_path": "/opt/dart-sdk",
+                "show_new_version": false,
+                "show_unused_code": true,
+                "show_unused_files": true,
+                "disable_baseline": true,
+                "enable_old_formatter": true,
+                "analyze_only_opened": true,
+                "excluded_folders": ["/work/app/build", "/work/app/gen"],
+                "log_file_path": "/work/app/.zed/dcm.log",
+            })
+        );
+    }
+
+    #[test]
+    fn workspace_configuration_golden() {
+        assert_eq!(
+            workspace_configuration(&settings(UserSettings::default())).unwrap(),
+            json!({
+                "dcm": {
+                    "dartSdkPath": null,
+                    "showNewVersion": true,
+                    "showUnusedCode": false,
+                    "showUnusedFiles": false,
+                    "disableBaseline": false,
+                    "enableOldFormatter": false,
+                    "analyzeOnlyOpened": false,
+                    "excludedFolders": [],
+                    "logFilePath": null,
+                }
+            })
+        );
     }
-    Ok(values.join(","))
 }
@@ -1,18 +1,40 @@
+mod analyze;
 mod commands;
 mod config;
+mod debug_log;
+mod doctor;
+mod error;
+mod history;
+mod host;
+mod jsonc;
 mod language_server;
+mod log_sessions;
+mod lsp_log;
+mod probe;
+mod schema;
+mod session;
+mod trust;
 mod utils;
+mod vscode;
 
-use config::ResolvedSettings;
-use zed_extension_api::{self as zed, LanguageServerId, SlashCommand, Worktree};
+use error::DcmError;
+use host::{Disk, Host, WorktreeHost};
+use session::SessionState;
+use zed_extension_api::{
+    self as zed, LanguageServerId, LanguageServerInstallationStatus, SlashCommand, Worktree,
+};
 
 const LANGUAGE_SERVER_ID: &str = "dcm";
 
-struct DcmExtension;
+struct DcmExtension {
+    session: SessionState,
+}
 
 impl zed::Extension for DcmExtension {
     fn new() -> Self {
-        Self
+        Self {
+            session: SessionState::default(),
+        }
     }
 
     fn language_server_command(
@@ -21,9 +43,19 @@ impl zed::Extension for DcmExtension {
         worktree: &Worktree,
     ) -> zed::Result<zed::Command> {
         ensure_supported_language_server(language_server_id)?;
+        let host = WorktreeHost::new(worktree);
+        self.session.remember_worktree(&host);
 
-        let settings = ResolvedSettings::from_worktree(worktree)?;
-        language_server::build_command(&settings)
+        zed::set_language_server_installation_status(
+            language_server_id,
+            &LanguageServerInstallationStatus::CheckingForUpdate,
+        );
+        let command = report_failure(language_server_id, self.launch_command(&host))?;
+        zed::set_language_server_installation_status(
+            language_server_id,
+            &LanguageServerInstallationStatus::None,
+        );
+        Ok(command)
     }
 
     fn language_server_initialization_options(
@@ -33,8 +65,11 @@ impl zed::Extension for DcmExtension {
     ) -> zed::Result<Option<serde_json::Value>> {
         ensure_supported_language_server(language_server_id)?;
 
-        let settings = ResolvedSettings::from_worktree(worktree)?;
-        Ok(Some(language_server::initialization_options(&settings)))
+        let settings = report_failure(
+            language_server_id,
+            self.session.resolve(&WorktreeHost::new(worktree)),
+        )?;
+        Ok(Some(language_server::initialization_options(&settings)?))
     }
 
     fn language_server_workspace_configuration(
@@ -44,8 +79,14 @@ impl zed::Extension for DcmExtension {
     ) -> zed::Result<Option<serde_json::Value>> {
         ensure_supported_language_server(language_server_id)?;
 
-        let settings = ResolvedSettings::from_worktree(worktree)?;
-        Ok(Some(language_server::workspace_configuration(&settings)?))
+        let settings = report_failure(
+            language_server_id,
+            self.session.resolve(&WorktreeHost::new(worktree)),
+        )?;
+        let configuration = language_server::workspace_configuration(&settings)?;
+        self.session
+            .record_configuration(worktree.id(), &settings.layered.user);
+        Ok(Some(configuration))
     }
 
     fn complete_slash_command_argument(
@@ -54,7 +95,8 @@ impl zed::Extension for DcmExtension {
         args: Vec<String>,
     ) -> zed::Result<Vec<zed::SlashCommandArgumentCompletion>> {
         if command.name == commands::DCM_SLASH_COMMAND {
-            Ok(commands::complete(&args))
+            let root = self.session.last_root();
+            Ok(commands::complete(&args, root.as_deref(), &Disk))
         } else {
             Ok(Vec::new())
         }
@@ -73,10 +115,76 @@ impl zed::Extension for DcmExtension {
             ));
         }
 
-        commands::run(args, worktree)
+        let host = worktree.map(WorktreeHost::new);
+        if let Some(host) = &host {
+            self.session.remember_worktree(host);
+        }
+        commands::run(
+            args,
+            host.as_ref().map(|host| host as &dyn Host),
+            &self.session,
+        )
+        .map_err(String::from)
+    }
+}
+
+impl DcmExtension {
+    /// Resolves the settings, checks trust and builds the server command,
+    /// recording the outcome in the debug log when it is enabled.
+    fn launch_command(&self, host: &dyn Host) -> Result<zed::Command, DcmError> {
+        let settings = self.session.resolve(host)?;
+        let root = settings.root_path.clone();
+        let debug = settings.layered.user.debug_log;
+        let launch = trust::ensure_trusted(host, &settings)
+            .and_then(|()| self.session.start_log_session(host, settings))
+            .and_then(|settings| {
+                let command = language_server::build_command(&settings)?;
+                Ok((settings, command))
+            });
+
+        match launch {
+            Ok((settings, command)) => {
+                if debug {
+                    debug_log::record(
+                        host,
+                        &root,
+                        "starting server",
+                        &debug_log::launch_details(&command),
+                    );
+                }
+                self.session
+                    .record_launch(host.id(), &settings.layered.user);
+                Ok(command)
+            }
+            Err(err) => {
+                if debug {
+                    debug_log::record(
+                        host,
+                        &root,
+                        "server not started",
+                        &[debug_log::error_line(&err)],
+                    );
+                }
+                Err(err)
+            }
+        }
     }
 }
 
+/// Shows a failed result, with its fix, in Zed's language server status before passing it on.
+fn report_failure<T>(
+    language_server_id: &LanguageServerId,
+    result: Result<T, DcmError>,
+) -> zed::Result<T> {
+    result.map_err(|err| {
+        zed::set_language_server_installation_status(
+            language_server_id,
+            &LanguageServerInstallationStatus::Failed(err.to_string()),
+        );
+        err.into()
+    })
+}
+
 fn ensure_supported_language_server(language_server_id: &LanguageServerId) -> zed::Result<()> {
     if language_server_id.as_ref() == LANGUAGE_SERVER_ID {
         Ok(())
@@ -0,0 +1,278 @@
+//! Timestamped per-session server logs in `log_directory`, with retention.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::DcmError;
+use crate::host::FileSystem;
+use crate::utils::unix_timestamp;
+
+const FILE_PREFIX: &str = "dcm-";
+const FILE_EXTENSION: &str = "log";
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// A session log file found in the log directory.
+#[derive(Debug, Clone)]
+pub struct SessionLog {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Last modification, in seconds since the Unix epoch.
+    pub modified: u64,
+}
+
+impl SessionLog {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// How many session logs, and how many bytes of them, to keep.
+#[derive(Copy, Clone, Debug)]
+pub struct Retention {
+    pub max_files: u64,
+    pub max_bytes: u64,
+}
+
+impl Retention {
+    pub fn new(max_files: u64, max_size_mb: u64) -> Self {
+        Self {
+            max_files,
+            max_bytes: max_size_mb.saturating_mul(BYTES_PER_MB),
+        }
+    }
+}
+
+/// Prunes `dir` to make room and returns the path for a new session's log.
+pub fn start(fs: &dyn FileSystem, dir: &Path, retention: Retention) -> Result<PathBuf, DcmError> {
+    fs.create_dir(dir)?;
+    let room = Retention {
+        max_files: retention.max_files.saturating_sub(1),
+        ..retention
+    };
+    prune(fs, dir, room, None)?;
+
+    let stem = format!("{FILE_PREFIX}{}", format_timestamp(unix_timestamp()));
+    let mut path = dir.join(format!("{stem}.{FILE_EXTENSION}"));
+    let mut suffix = 2;
+    while fs.metadata(&path).is_some() {
+        path = dir.join(format!("{stem}-{suffix}.{FILE_EXTENSION}"));
+        suffix += 1;
+    }
+    Ok(path)
+}
+
+/// Returns the session logs in `dir`, newest first.
+pub fn list(fs: &dyn FileSystem, dir: &Path) -> Vec<SessionLog> {
+    let mut logs: Vec<SessionLog> = fs
+        .read_dir(dir)
+        .into_iter()
+        .filter(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            name.starts_with(FILE_PREFIX) && name.ends_with(&format!(".{FILE_EXTENSION}"))
+        })
+        .filter_map(|path| {
+            let metadata = fs.metadata(&path).filter(|metadata| !metadata.is_dir)?;
+            Some(SessionLog {
+                path,
+                size: metadata.len,
+                modified: metadata.modified,
+            })
+        })
+        .collect();
+    logs.sort_by_key(|log| std::cmp::Reverse(start_order(&log.name())));
+    logs
+}
+
+/// Orders names by the start time they embed, then by the `-N` suffix added
+/// when two sessions start in the same second.
+fn start_order(name: &str) -> (String, u64) {
+    let stem = name
+        .trim_start_matches(FILE_PREFIX)
+        .trim_end_matches(FILE_EXTENSION)
+        .trim_end_matches('.');
+    // `YYYYMMDD-HHMMSS` is 15 characters.
+    match stem.get(15..).and_then(|rest| rest.strip_prefix('-')) {
+        Some(suffix) => match suffix.parse() {
+            Ok(suffix) => (stem[..15].to_string(), suffix),
+            Err(_) => (stem.to_string(), 1),
+        },
+        None => (stem.to_string(), 1),
+    }
+}
+
+/// Deletes the oldest session logs until `retention` is met, returning the deleted ones.
+///
+/// `keep` is never deleted, so the log of a server that is still running survives.
+pub fn prune(
+    fs: &dyn FileSystem,
+    dir: &Path,
+    retention: Retention,
+    keep: Option<&Path>,
+) -> Result<Vec<SessionLog>, DcmError> {
+    let mut kept_files = 0;
+    let mut kept_bytes = 0;
+    let mut full = false;
+    let mut removed = Vec::new();
+    for log in list(fs, dir) {
+        let protected = keep == Some(log.path.as_path());
+        // Once one log does not fit, every older one goes too.
+        full = full
+            || kept_files >= retention.max_files
+            || kept_bytes + log.size > retention.max_bytes;
+        if protected || !full {
+            kept_files += 1;
+            kept_bytes += log.size;
+            continue;
+        }
+        fs.remove_file(&log.path)?;
+        removed.push(log);
+    }
+    Ok(removed)
+}
+
+/// Formats Unix seconds as `YYYYMMDD-HHMMSS` in UTC.
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = seconds / 86_400;
+    let time = seconds % 86_400;
+
+    // Civil date from days since the epoch, after Howard Hinnant's algorithm.
+    let shifted = days as i64 + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::fake::FakeHost;
+    use crate::host::FileSystem;
+
+    const DIR: &str = "/work/app/.zed/dcm-logs";
+
+    fn logs(names: &[(&str, usize)]) -> FakeHost {
+        names.iter().fold(FakeHost::new(), |host, (name, size)| {
+            host.with_file(&format!("{DIR}/{name}"), &"x".repeat(*size))
+        })
+    }
+
+    fn names(logs: &[SessionLog]) -> Vec<String> {
+        logs.iter().map(SessionLog::name).collect()
+    }
+
+    #[test]
+    fn lists_newest_first_with_same_second_suffixes() {
+        let host = logs(&[
+            ("dcm-20260101-120000.log", 1),
+            ("dcm-20260101-120000-2.log", 1),
+            ("dcm-20260101-120000-10.log", 1),
+            ("dcm-20251231-235959.log", 1),
+            ("notes.txt", 1),
+        ]);
+
+        assert_eq!(
+            names(&list(&host, Path::new(DIR))),
+            [
+                "dcm-20260101-120000-10.log",
+                "dcm-20260101-120000-2.log",
+                "dcm-20260101-120000.log",
+                "dcm-20251231-235959.log",
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_files() {
+        let host = logs(&[
+            ("dcm-20260101-000001.log", 1),
+            ("dcm-20260101-000002.log", 1),
+            ("dcm-20260101-000003.log", 1),
+        ]);
+        let retention = Retention {
+            max_files: 2,
+            max_bytes: u64::MAX,
+        };
+
+        let removed = prune(&host, Path::new(DIR), retention, None).unwrap();
+        assert_eq!(names(&removed), ["dcm-20260101-000001.log"]);
+        assert_eq!(list(&host, Path::new(DIR)).len(), 2);
+    }
+
+    #[test]
+    fn prune_drops_everything_older_than_the_byte_limit() {
+        let host = logs(&[
+            ("dcm-20260101-000001.log", 1),
+            ("dcm-20260101-000002.log", 6),
+            ("dcm-20260101-000003.log", 4),
+        ]);
+        let retention = Retention {
+            max_files: 10,
+            max_bytes: 8,
+        };
+
+        let removed = prune(&host, Path::new(DIR), retention, None).unwrap();
+        assert_eq!(
+            names(&removed),
+            ["dcm-20260101-000002.log", "dcm-20260101-000001.log"]
+        );
+    }
+
+    #[test]
+    fn prune_never_deletes_the_running_session() {
+        let host = logs(&[
+            ("dcm-20260101-000001.log", 1),
+            ("dcm-20260101-000002.log", 1),
+        ]);
+        let keep = Path::new(DIR).join("dcm-20260101-000001.log");
+
+        let removed = prune(&host, Path::new(DIR), Retention::new(0, 0), Some(&keep)).unwrap();
+        assert_eq!(names(&removed), ["dcm-20260101-000002.log"]);
+        assert!(host.is_file(&keep));
+    }
+
+    #[test]
+    fn start_leaves_room_for_the_new_log() {
+        let host = logs(&[
+            ("dcm-20260101-000001.log", 1),
+            ("dcm-20260101-000002.log", 1),
+        ]);
+
+        let path = start(&host, Path::new(DIR), Retention::new(2, 1)).unwrap();
+        assert_eq!(
+            names(&list(&host, Path::new(DIR))),
+            ["dcm-20260101-000002.log"]
+        );
+        assert!(path.starts_with(DIR));
+        assert!(!host.is_file(&path));
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(format_timestamp(0), "19700101-000000");
+        assert_eq!(format_timestamp(951_782_400), "20000229-000000");
+        assert_eq!(format_timestamp(1_767_225_599), "20251231-235959");
+    }
+}
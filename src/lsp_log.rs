@@ -0,0 +1,380 @@
+//! Summaries of the JSON-RPC traffic the server writes to its `--logs` file.
+//!
+//! The parser is deliberately forgiving about the line format: each line may
+//! start with a timestamp (`2024-05-01 10:00:00.123`, `2024-05-01T10:00:00.123Z`
+//! or `10:00:00.123`, optionally in brackets) and carries at most one JSON
+//! message, found by its first `{`. Lines without a message are scanned for
+//! exceptions and stack traces.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+/// Entries kept for the slowest-request and diagnostics tables.
+const TOP_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct TimedRequest {
+    pub method: String,
+    pub id: String,
+    pub millis: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MethodStats {
+    pub count: usize,
+    pub total_millis: u64,
+    pub max_millis: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrorResponse {
+    pub method: String,
+    pub code: Option<i64>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticsStats {
+    pub publishes: usize,
+    /// Number of diagnostics in the most recent publish.
+    pub latest: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogSummary {
+    pub messages: usize,
+    /// Milliseconds between the first and last timestamped lines.
+    pub span_millis: Option<u64>,
+    /// Slowest individual requests, slowest first.
+    pub slowest: Vec<TimedRequest>,
+    pub by_method: BTreeMap<String, MethodStats>,
+    pub errors: Vec<ErrorResponse>,
+    /// Requests that never got a response, as `method (id)`.
+    pub unanswered: Vec<String>,
+    /// The first line of each exception or crash report.
+    pub exceptions: Vec<String>,
+    pub diagnostics: BTreeMap<String, DiagnosticsStats>,
+}
+
+impl LogSummary {
+    /// Files with the most diagnostic publishes, most first.
+    pub fn busiest_files(&self) -> Vec<(&String, &DiagnosticsStats)> {
+        let mut files: Vec<_> = self.diagnostics.iter().collect();
+        files.sort_by(|a, b| b.1.publishes.cmp(&a.1.publishes).then(a.0.cmp(b.0)));
+        files.truncate(TOP_ENTRIES);
+        files
+    }
+}
+
+/// Pairs requests with responses and collects errors, exceptions and diagnostics.
+pub fn summarize(text: &str) -> LogSummary {
+    let mut summary = LogSummary::default();
+    // Keyed by the id's JSON, so `1` and `"1"` stay different requests.
+    let mut pending: HashMap<String, (String, Option<u64>)> = HashMap::new();
+    let mut timed = Vec::new();
+    let mut first_time = None;
+    let mut last_time = None;
+    let mut in_stack_trace = false;
+
+    for line in text.lines() {
+        let time = parse_timestamp(line);
+        if time.is_some() {
+            first_time = first_time.or(time);
+            last_time = time;
+        }
+
+        let Some(message) = parse_message(line) else {
+            let trimmed = line.trim();
+            let exception = is_exception(trimmed);
+            let is_frame = trimmed.starts_with('#') || trimmed.starts_with("at ");
+            let mut folded = false;
+            match summary.exceptions.last_mut() {
+                // `Unhandled exception:` is usually followed by the error itself.
+                Some(last)
+                    if in_stack_trace
+                        && last.ends_with(':')
+                        && !is_frame
+                        && !trimmed.is_empty() =>
+                {
+                    last.push(' ');
+                    last.push_str(trimmed);
+                    folded = true;
+                }
+                _ if exception => summary.exceptions.push(trimmed.to_string()),
+                _ => {}
+            }
+            in_stack_trace = exception || folded || (in_stack_trace && is_frame);
+            continue;
+        };
+        in_stack_trace = false;
+        summary.messages += 1;
+
+        let id = message
+            .get("id")
+            .filter(|id| !id.is_null())
+            .map(Value::to_string);
+        let method = message.get("method").and_then(Value::as_str);
+        match (id, method) {
+            (Some(id), Some(method)) => {
+                pending.insert(id, (method.to_string(), time));
+            }
+            (Some(id), None) => {
+                let Some((method, started)) = pending.remove(&id) else {
+                    continue;
+                };
+                if let Some(error) = message.get("error") {
+                    summary.errors.push(ErrorResponse {
+                        method: method.clone(),
+                        code: error.get("code").and_then(Value::as_i64),
+                        message: error
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_string(),
+                    });
+                }
+                if let (Some(started), Some(finished)) = (started, time) {
+                    let millis = finished.saturating_sub(started);
+                    let stats = summary.by_method.entry(method.clone()).or_default();
+                    stats.count += 1;
+                    stats.total_millis += millis;
+                    stats.max_millis = stats.max_millis.max(millis);
+                    timed.push(TimedRequest {
+                        method,
+                        id: id_label(&id),
+                        millis,
+                    });
+                }
+            }
+            (None, Some("textDocument/publishDiagnostics")) => {
+                let params = message.get("params");
+                let Some(uri) = params.and_then(|p| p.get("uri")).and_then(Value::as_str) else {
+                    continue;
+                };
+                let count = params
+                    .and_then(|p| p.get("diagnostics"))
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len);
+                let stats = summary.diagnostics.entry(uri.to_string()).or_default();
+                stats.publishes += 1;
+                stats.latest = count;
+            }
+            _ => {}
+        }
+    }
+
+    timed.sort_by_key(|request| Reverse(request.millis));
+    timed.truncate(TOP_ENTRIES);
+    summary.slowest = timed;
+
+    let mut unanswered: Vec<String> = pending
+        .into_iter()
+        .map(|(id, (method, _))| format!("{method} ({})", id_label(&id)))
+        .collect();
+    unanswered.sort();
+    summary.unanswered = unanswered;
+
+    if let (Some(first), Some(last)) = (first_time, last_time) {
+        summary.span_millis = Some(last.saturating_sub(first));
+    }
+    summary
+}
+
+fn parse_message(line: &str) -> Option<Value> {
+    let start = line.find('{')?;
+    let value: Value = serde_json::from_str(line[start..].trim_end()).ok()?;
+    let is_rpc = value.get("jsonrpc").is_some()
+        || value.get("method").is_some()
+        || value.get("result").is_some()
+        || value.get("error").is_some();
+    is_rpc.then_some(value)
+}
+
+/// Shows string ids without their quotes.
+fn id_label(key: &str) -> String {
+    match serde_json::from_str(key) {
+        Ok(Value::String(id)) => id,
+        _ => key.to_string(),
+    }
+}
+
+fn is_exception(line: &str) -> bool {
+    [
+        "Unhandled exception",
+        "Exception:",
+        "Error:",
+        "StackOverflow",
+        "Segmentation fault",
+    ]
+    .iter()
+    .any(|marker| line.contains(marker))
+        && !line.contains('{')
+}
+
+/// Reads a leading timestamp as milliseconds, counting days when a date is present.
+fn parse_timestamp(line: &str) -> Option<u64> {
+    let text = line.trim_start().trim_start_matches('[');
+    let (days, rest) = match parse_date(text) {
+        Some(days) => (days, text.get(10..)?.trim_start_matches(['T', ' '])),
+        None => (0, text),
+    };
+
+    let digits = |range: std::ops::Range<usize>| -> Option<u64> {
+        let part = rest.get(range)?;
+        part.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| part.parse().ok())?
+    };
+    if rest.get(2..3) != Some(":") || rest.get(5..6) != Some(":") {
+        return None;
+    }
+    let (hours, minutes, seconds) = (digits(0..2)?, digits(3..5)?, digits(6..8)?);
+    let fraction: String = rest
+        .get(8..)
+        .filter(|tail| tail.starts_with('.'))
+        .map(|tail| tail[1..].chars().take_while(char::is_ascii_digit).collect())
+        .unwrap_or_default();
+    let millis = format!("{fraction:0<3}")[..3].parse::<u64>().unwrap_or(0);
+
+    Some((((days * 24 + hours) * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+/// Parses `YYYY-MM-DD` at the start of `text` into days since the Unix epoch.
+fn parse_date(text: &str) -> Option<u64> {
+    let date = text.get(..10)?;
+    let mut parts = date.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days from civil date, after Howard Hinnant's algorithm.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let shifted_month = (month + 9) % 12;
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    u64::try_from(era * 146_097 + day_of_era - 719_468).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_numeric_and_string_ids_separately() {
+        let log = r#"10:00:00.000 {"jsonrpc":"2.0","id":1,"method":"initialize"}
+10:00:00.250 {"jsonrpc":"2.0","id":"1","method":"workspace/configuration"}
+10:00:01.500 {"jsonrpc":"2.0","id":1,"result":{}}
+10:00:02.000 {"jsonrpc":"2.0","id":"1","result":[]}"#;
+
+        let summary = summarize(log);
+        assert_eq!(summary.messages, 4);
+        assert!(summary.unanswered.is_empty());
+        assert_eq!(summary.slowest[0].method, "workspace/configuration");
+        assert_eq!(summary.slowest[0].id, "1");
+        assert_eq!(summary.slowest[0].millis, 1750);
+        assert_eq!(summary.by_method["initialize"].max_millis, 1500);
+        assert_eq!(summary.span_millis, Some(2000));
+    }
+
+    #[test]
+    fn collects_error_responses() {
+        let log = r#"{"jsonrpc":"2.0","id":7,"method":"textDocument/hover"}
+{"jsonrpc":"2.0","id":7,"error":{"code":-32603,"message":"boom"}}"#;
+
+        let summary = summarize(log);
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(summary.errors[0].method, "textDocument/hover");
+        assert_eq!(summary.errors[0].code, Some(-32603));
+        assert_eq!(summary.errors[0].message, "boom");
+        assert!(summary.by_method.is_empty());
+    }
+
+    #[test]
+    fn lists_unanswered_requests() {
+        let log = r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}
+{"jsonrpc":"2.0","id":"a-1","method":"textDocument/codeAction"}
+{"jsonrpc":"2.0","id":9,"result":null}
+{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+
+        assert_eq!(
+            summarize(log).unanswered,
+            ["shutdown (2)", "textDocument/codeAction (a-1)"]
+        );
+    }
+
+    #[test]
+    fn reads_each_timestamp_format() {
+        assert_eq!(parse_timestamp("10:00:01.5 message"), Some(36_001_500));
+        assert_eq!(parse_timestamp("[10:00:01] message"), Some(36_001_000));
+        let day = 19_844 * 86_400_000;
+        assert_eq!(
+            parse_timestamp("2024-05-01 10:00:01.123 message"),
+            Some(day + 36_001_123)
+        );
+        assert_eq!(
+            parse_timestamp("[2024-05-01T10:00:01.123456Z] message"),
+            Some(day + 36_001_123)
+        );
+        assert_eq!(parse_timestamp("no time here"), None);
+        assert_eq!(parse_timestamp("2024-13-01 10:00:00"), None);
+    }
+
+    #[test]
+    fn spans_midnight_when_dates_are_present() {
+        let log = r#"2024-05-01 23:59:59.900 {"jsonrpc":"2.0","id":1,"method":"initialize"}
+2024-05-02 00:00:00.100 {"jsonrpc":"2.0","id":1,"result":{}}"#;
+
+        assert_eq!(summarize(log).slowest[0].millis, 200);
+    }
+
+    #[test]
+    fn folds_stack_traces_into_one_exception() {
+        let log = "Unhandled exception:
+Bad state: No element
+#0      List.first (dart:core-patch/growable_array.dart:343:5)
+#1      main (file:///app/bin/dcm.dart:10:3)
+Error: license expired
+plain output";
+
+        assert_eq!(
+            summarize(log).exceptions,
+            [
+                "Unhandled exception: Bad state: No element",
+                "Error: license expired",
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_diagnostics_per_file() {
+        let publish = |uri: &str, count: usize| {
+            let diagnostics = vec![serde_json::json!({ "message": "x" }); count];
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri, "diagnostics": diagnostics },
+            })
+            .to_string()
+        };
+        let log = [
+            publish("file:///a.dart", 3),
+            publish("file:///b.dart", 1),
+            publish("file:///a.dart", 2),
+        ]
+        .join("\n");
+
+        let summary = summarize(&log);
+        let busiest = summary.busiest_files();
+        assert_eq!(busiest[0].0, "file:///a.dart");
+        assert_eq!(busiest[0].1.publishes, 2);
+        assert_eq!(busiest[0].1.latest, 2);
+        assert_eq!(busiest[1].1.latest, 1);
+    }
+}
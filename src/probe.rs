@@ -0,0 +1,453 @@
+//! `/dcm probe`: start the language server once and run an LSP handshake.
+//!
+//! Zed's process API cannot write to a child's stdin, so the server command is
+//! wrapped in `/bin/sh`, which pipes the framed `initialize`, `initialized`,
+//! `shutdown` and `exit` messages from an environment variable into it and
+//! kills it if it has not exited before the timeout. The wrapper also notes
+//! when the server first writes to stdout, which is how long `initialize` took
+//! when that first message is its reply. That needs a POSIX shell, so the probe
+//! is not available on Windows.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use zed_extension_api::{Command, Os};
+
+use crate::config::ResolvedSettings;
+use crate::error::DcmError;
+use crate::host::Host;
+use crate::language_server;
+use crate::utils::path_to_string;
+
+/// Shell the server runs under, since Zed cannot write to a child's stdin.
+pub const SHELL: &str = "/bin/sh";
+
+/// Seconds the server gets to answer before it is killed.
+pub const PROBE_TIMEOUT_SECS: u64 = 20;
+
+const INPUT_VAR: &str = "DCM_PROBE_INPUT";
+const TIMEOUT_VAR: &str = "DCM_PROBE_TIMEOUT";
+
+/// Exit status the wrapper reports when it had to kill the server.
+const TIMED_OUT_STATUS: i32 = 124;
+
+const INITIALIZE_ID: u64 = 1;
+const SHUTDOWN_ID: u64 = 2;
+
+/// Start of the stderr line the wrapper writes when the server first writes to stdout.
+const FIRST_OUTPUT_MARKER: &str = "dcm-probe: first output after ";
+
+/// Runs `"$@"` with the handshake on stdin, killing it after the timeout, and
+/// passes its stdout through `stamp`, which writes [`FIRST_OUTPUT_MARKER`] to
+/// stderr once the first byte arrives. `date +%N` is not portable, so systems
+/// without a sub-second clock get no timing. The exit status of `serve` travels
+/// back over fd 3 because POSIX `sh` has no `pipefail`.
+const WRAPPER_SCRIPT: &str = r#"now() {
+  t=$(date +%s%N 2>/dev/null)
+  case $t in ''|*[!0-9]*) ;; *) echo "${t%??????}" ;; esac
+}
+serve() {
+  printf '%s' "$DCM_PROBE_INPUT" | "$@" 3>&- 4>&- &
+  server=$!
+  ( sleep "$DCM_PROBE_TIMEOUT"; kill "$server" ) >/dev/null 2>&1 3>&- 4>&- &
+  watchdog=$!
+  wait "$server"
+  status=$?
+  if kill -0 "$watchdog" 2>/dev/null; then
+    kill "$watchdog" 2>/dev/null
+    return "$status"
+  fi
+  return 124
+}
+stamp() {
+  first=$(dd bs=1 count=1 2>/dev/null)
+  [ -n "$first" ] || return 0
+  end=$(now)
+  if [ -n "$start" ] && [ -n "$end" ]; then
+    printf 'dcm-probe: first output after %s ms\n' $((end - start)) >&2
+  fi
+  printf '%s' "$first"
+  cat
+}
+exec 4>&1
+start=$(now)
+status=$( { { serve "$@"; echo "$?" >&3; } | stamp >&4; } 3>&1 )
+exit "$status"
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// The server answered `initialize` with a result.
+    Initialized,
+    /// The server answered `initialize` with an error.
+    InitializeFailed(String),
+    /// The server exited without answering `initialize`.
+    NoResponse,
+    /// The server was still running when the timeout expired.
+    TimedOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeReport {
+    pub outcome: ProbeOutcome,
+    pub capabilities: Option<Value>,
+    pub server_info: Option<Value>,
+    pub shutdown_acknowledged: bool,
+    pub exit_status: Option<i32>,
+    /// Text of `window/logMessage` and `window/showMessage` notifications.
+    pub messages: Vec<String>,
+    pub stderr: String,
+    /// Time from starting the server until it first wrote to stdout, when that
+    /// first message was the `initialize` reply.
+    pub initialize_time: Option<Duration>,
+    /// Time from spawning the wrapper until it exited, so it covers the whole
+    /// session through `shutdown` rather than `initialize` alone.
+    pub session_time: Duration,
+}
+
+/// Starts the server with the command [`language_server::build_command`] builds,
+/// run through the `/bin/sh` wrapper, and probes it.
+pub fn run(host: &dyn Host, settings: &ResolvedSettings) -> Result<ProbeReport, DcmError> {
+    if matches!(host.platform().0, Os::Windows) || !host.is_file(Path::new(SHELL)) {
+        return Err(DcmError::usage(format!(
+            "`/dcm probe` needs {SHELL} to feed the server its input, and this system has none. Run `dcm start-server` in a terminal to check the server instead."
+        )));
+    }
+    let server = language_server::build_command(settings)?;
+    let input = handshake_input(
+        &settings.root_path,
+        language_server::initialization_options(settings)?,
+    )?;
+    let command = wrap(&server, &input, PROBE_TIMEOUT_SECS);
+
+    let started = Instant::now();
+    let output = host.run(&command)?;
+    Ok(parse_output(
+        output.status,
+        &output.stdout,
+        &output.stderr,
+        started.elapsed(),
+    ))
+}
+
+/// Wraps `server` so the handshake in `input` is piped to its stdin.
+pub fn wrap(server: &Command, input: &str, timeout_secs: u64) -> Command {
+    Command::new(SHELL)
+        .arg("-c")
+        .arg(WRAPPER_SCRIPT)
+        .arg("dcm-probe")
+        .arg(server.command.clone())
+        .args(server.args.iter().cloned())
+        .envs(server.env.iter().cloned())
+        .env(INPUT_VAR, input)
+        .env(TIMEOUT_VAR, timeout_secs.to_string())
+}
+
+/// Builds the framed messages a client sends to open and close a session.
+pub fn handshake_input(root: &Path, initialization_options: Value) -> Result<String, DcmError> {
+    let root_path = path_to_string(root)?;
+    let root_uri = file_uri(&root_path);
+    let name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root_path.clone());
+
+    let messages = [
+        json!({
+            "jsonrpc": "2.0",
+            "id": INITIALIZE_ID,
+            "method": "initialize",
+            "params": {
+                "processId": null,
+                "clientInfo": { "name": "zed-dcm-probe" },
+                "rootUri": root_uri,
+                "workspaceFolders": [{ "uri": root_uri, "name": name }],
+                "capabilities": {},
+                "initializationOptions": initialization_options,
+            },
+        }),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": SHUTDOWN_ID, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ];
+    Ok(messages.iter().map(frame).collect())
+}
+
+/// Builds a `file://` URI, percent-encoding everything but unreserved characters and `/`.
+fn file_uri(path: &str) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+/// Encodes a JSON-RPC message with its `Content-Length` header.
+pub fn frame(message: &Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+/// Interprets what the wrapped server wrote before it exited.
+pub fn parse_output(
+    status: Option<i32>,
+    stdout: &[u8],
+    stderr: &[u8],
+    session_time: Duration,
+) -> ProbeReport {
+    let mut first_output = None;
+    let stderr = String::from_utf8_lossy(stderr)
+        .lines()
+        .filter(|line| match line.strip_prefix(FIRST_OUTPUT_MARKER) {
+            Some(rest) => {
+                first_output = rest
+                    .strip_suffix(" ms")
+                    .and_then(|ms| ms.parse().ok())
+                    .map(Duration::from_millis);
+                false
+            }
+            None => true,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let messages = read_messages(stdout);
+    let answered_first = messages.first().is_some_and(|message| {
+        message.get("id").and_then(Value::as_u64) == Some(INITIALIZE_ID)
+            && message.get("method").is_none()
+    });
+
+    let mut report = ProbeReport {
+        outcome: ProbeOutcome::NoResponse,
+        capabilities: None,
+        server_info: None,
+        shutdown_acknowledged: false,
+        exit_status: status,
+        messages: Vec::new(),
+        stderr,
+        initialize_time: first_output.filter(|_| answered_first),
+        session_time,
+    };
+
+    for message in messages {
+        let id = message.get("id").and_then(Value::as_u64);
+        match (id, message.get("method").and_then(Value::as_str)) {
+            (Some(INITIALIZE_ID), None) => {
+                if let Some(error) = message.get("error") {
+                    let text = error
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                        .unwrap_or_else(|| error.to_string());
+                    report.outcome = ProbeOutcome::InitializeFailed(text);
+                } else if let Some(result) = message.get("result") {
+                    report.outcome = ProbeOutcome::Initialized;
+                    report.capabilities = result.get("capabilities").cloned();
+                    report.server_info = result.get("serverInfo").cloned();
+                }
+            }
+            (Some(SHUTDOWN_ID), None) => report.shutdown_acknowledged = true,
+            (_, Some("window/logMessage" | "window/showMessage")) => {
+                if let Some(text) = message.pointer("/params/message").and_then(Value::as_str) {
+                    report.messages.push(text.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if status == Some(TIMED_OUT_STATUS) && report.outcome == ProbeOutcome::NoResponse {
+        report.outcome = ProbeOutcome::TimedOut;
+    }
+    report
+}
+
+/// Splits `Content-Length` framed output into JSON messages, stopping at the first malformed one.
+fn read_messages(mut bytes: &[u8]) -> Vec<Value> {
+    let mut messages = Vec::new();
+    while let Some(header_end) = find(bytes, b"\r\n\r\n") {
+        let header = String::from_utf8_lossy(&bytes[..header_end]);
+        let Some(length) = header.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        }) else {
+            break;
+        };
+
+        let body_start = header_end + 4;
+        let Some(body) = bytes.get(body_start..body_start + length) else {
+            break;
+        };
+        if let Ok(message) = serde_json::from_slice(body) {
+            messages.push(message);
+        }
+        bytes = &bytes[body_start + length..];
+    }
+    messages
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Writes an executable shell script standing in for `dcm start-server`.
+    fn stub_server(name: &str, body: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dcm-probe-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dcm");
+        fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn probe(server: &Path, timeout_secs: u64) -> ProbeReport {
+        let server = Command::new(server.to_string_lossy())
+            .args(["start-server", "--root-folder=/work/app", "--client=zed"])
+            .env("DCM_STUB", "1");
+        let input = handshake_input(Path::new("/work/app"), json!({})).unwrap();
+        let command = wrap(&server, &input, timeout_secs);
+
+        let started = Instant::now();
+        let output = std::process::Command::new(&command.command)
+            .args(&command.args)
+            .envs(command.env.iter().cloned())
+            .output()
+            .unwrap();
+        parse_output(
+            output.status.code(),
+            &output.stdout,
+            &output.stderr,
+            started.elapsed(),
+        )
+    }
+
+    fn replies(messages: &[Value]) -> String {
+        messages.iter().map(frame).collect()
+    }
+
+    #[test]
+    fn reports_capabilities_and_stderr_from_a_healthy_server() {
+        let output = replies(&[
+            json!({ "jsonrpc": "2.0", "method": "window/logMessage", "params": { "type": 3, "message": "analysis started" } }),
+            json!({ "jsonrpc": "2.0", "id": 1, "result": {
+                "capabilities": { "hoverProvider": true },
+                "serverInfo": { "name": "dcm", "version": "1.27.0" },
+            } }),
+            json!({ "jsonrpc": "2.0", "id": 2, "result": null }),
+        ]);
+        let server = stub_server(
+            "healthy",
+            &format!(
+                "input=$(cat)\necho \"args: $* env: $DCM_STUB\" >&2\ncase \"$input\" in *'\"initialize\"'*'\"shutdown\"'*'\"exit\"'*) ;; *) exit 3 ;; esac\nprintf '%s' '{output}'"
+            ),
+        );
+
+        let report = probe(&server, 10);
+        assert_eq!(report.outcome, ProbeOutcome::Initialized);
+        assert_eq!(report.capabilities, Some(json!({ "hoverProvider": true })));
+        assert_eq!(report.server_info.unwrap()["version"], "1.27.0");
+        assert!(report.shutdown_acknowledged);
+        assert_eq!(report.exit_status, Some(0));
+        assert_eq!(report.messages, ["analysis started"]);
+        assert_eq!(report.initialize_time, None);
+        assert_eq!(
+            report.stderr.trim(),
+            "args: start-server --root-folder=/work/app --client=zed env: 1"
+        );
+    }
+
+    #[test]
+    fn reports_initialize_errors() {
+        let output = replies(&[json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32603, "message": "license expired" },
+        })]);
+        let server = stub_server(
+            "error",
+            &format!("cat >/dev/null\nprintf '%s' '{output}'\nexit 1"),
+        );
+
+        let report = probe(&server, 10);
+        assert_eq!(
+            report.outcome,
+            ProbeOutcome::InitializeFailed("license expired".to_string())
+        );
+        assert!(!report.shutdown_acknowledged);
+        assert_eq!(report.exit_status, Some(1));
+        assert!(report.stderr.is_empty());
+        if cfg!(target_os = "linux") {
+            assert!(report.initialize_time.is_some());
+        }
+    }
+
+    #[test]
+    fn times_initialize_from_the_first_output() {
+        let stdout = frame(&json!({ "id": 1, "result": { "capabilities": {} } }));
+        let stderr = format!("starting\n{FIRST_OUTPUT_MARKER}840 ms\n");
+
+        let report = parse_output(
+            Some(0),
+            stdout.as_bytes(),
+            stderr.as_bytes(),
+            Duration::from_secs(2),
+        );
+        assert_eq!(report.initialize_time, Some(Duration::from_millis(840)));
+        assert_eq!(report.stderr, "starting");
+    }
+
+    #[test]
+    fn reports_a_server_that_crashes_before_answering() {
+        let server = stub_server("crash", "echo 'Segmentation fault' >&2\nexit 139");
+
+        let report = probe(&server, 10);
+        assert_eq!(report.outcome, ProbeOutcome::NoResponse);
+        assert_eq!(report.exit_status, Some(139));
+        assert!(report.stderr.contains("Segmentation fault"));
+    }
+
+    #[test]
+    fn kills_a_server_that_hangs() {
+        let server = stub_server("hang", "cat >/dev/null\nexec sleep 30");
+
+        let report = probe(&server, 1);
+        assert_eq!(report.outcome, ProbeOutcome::TimedOut);
+        assert!(report.session_time < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn encodes_the_root_uri() {
+        let input = handshake_input(Path::new("/work/my app#2/ü"), json!({})).unwrap();
+        assert!(input.contains(r#""rootUri":"file:///work/my%20app%232/%C3%BC""#));
+        assert!(input.contains(r#""name":"ü""#));
+    }
+
+    #[test]
+    fn reads_messages_split_by_content_length() {
+        let stdout = format!(
+            "{}Content-Type: application/vscode-jsonrpc\r\n{}",
+            frame(&json!({ "id": 2, "result": null })),
+            frame(&json!({ "id": 1, "result": { "capabilities": {} } }))
+        );
+        let messages = read_messages(stdout.as_bytes());
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["id"], 1);
+    }
+}
@@ -0,0 +1,625 @@
+//! The single table of supported `dcm` settings and how each one reaches the
+//! server, plus validation of settings blocks against it.
+
+use std::path::PathBuf;
+
+use serde_json::{json, Map, Value};
+
+use crate::config::{ResolvedSettings, PROFILES_KEY, PROFILE_KEY};
+use crate::error::DcmError;
+use crate::utils::{closest_match, path_to_string};
+
+/// The JSON shape a setting accepts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SettingKind {
+    Bool,
+    Path,
+    PathList,
+    /// A whole number of at least 1.
+    Count,
+}
+
+impl SettingKind {
+    pub fn accepts(self, value: &Value) -> bool {
+        match self {
+            SettingKind::Bool => value.is_boolean(),
+            SettingKind::Path => value.is_string() || value.is_null(),
+            SettingKind::PathList => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(Value::is_string)),
+            SettingKind::Count => value.as_u64().is_some_and(|count| count >= 1),
+        }
+    }
+
+    /// Parses command-line words into a value of this kind.
+    pub fn parse_args(self, args: &[String]) -> Result<Value, String> {
+        match self {
+            SettingKind::Bool => match args {
+                [value] => match value.to_ascii_lowercase().as_str() {
+                    "true" | "on" | "yes" => Ok(Value::Bool(true)),
+                    "false" | "off" | "no" => Ok(Value::Bool(false)),
+                    other => Err(format!("expected true or false, found `{other}`")),
+                },
+                _ => Err("expected a single true or false value".to_string()),
+            },
+            SettingKind::Path => {
+                let joined = args.join(" ");
+                if joined.trim().is_empty() {
+                    Err("expected a path".to_string())
+                } else {
+                    Ok(Value::String(joined.trim().to_string()))
+                }
+            }
+            SettingKind::PathList => Ok(Value::Array(
+                args.iter()
+                    .flat_map(|arg| arg.split(','))
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+            )),
+            SettingKind::Count => match args {
+                [value] => match value.parse::<u64>() {
+                    Ok(count) if count >= 1 => Ok(Value::from(count)),
+                    _ => Err(format!(
+                        "expected a whole number of at least 1, found `{value}`"
+                    )),
+                },
+                _ => Err("expected a single number".to_string()),
+            },
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            SettingKind::Bool => "a boolean",
+            SettingKind::Path => "a path string",
+            SettingKind::PathList => "an array of path strings",
+            SettingKind::Count => "a whole number of at least 1",
+        }
+    }
+}
+
+/// How a setting is passed to `dcm start-server`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CliFlag {
+    /// Not passed on the command line.
+    None,
+    /// Passed bare when the setting is `true`.
+    IfTrue(&'static str),
+    /// Passed bare when the setting is `false`.
+    IfFalse(&'static str),
+    /// Passed as `--flag=value` when set; lists are comma-separated and skipped when empty.
+    Value(&'static str),
+}
+
+/// Built-in default of a setting.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SettingDefault {
+    Bool(bool),
+    Unset,
+    EmptyList,
+    Count(u64),
+}
+
+impl SettingDefault {
+    pub fn to_value(self) -> Value {
+        match self {
+            SettingDefault::Bool(value) => Value::Bool(value),
+            SettingDefault::Unset => Value::Null,
+            SettingDefault::EmptyList => Value::Array(Vec::new()),
+            SettingDefault::Count(count) => Value::from(count),
+        }
+    }
+}
+
+/// The `/dcm toggle` entry for a boolean setting.
+pub struct ToggleSpec {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub enabled: &'static str,
+    pub disabled: &'static str,
+}
+
+/// A single `dcm` setting and every place it is translated to.
+pub struct SettingSpec {
+    /// snake_case key used in settings files and initialization options.
+    pub key: &'static str,
+    /// camelCase key used in workspace configuration and VS Code settings.
+    pub camel_key: &'static str,
+    pub kind: SettingKind,
+    pub default: SettingDefault,
+    pub description: &'static str,
+    pub flag: CliFlag,
+    /// Whether the value is sent in initialization options and workspace configuration.
+    pub sent_to_server: bool,
+    /// Whether a running server only picks up a change after a restart.
+    pub requires_restart: bool,
+    /// Oldest DCM release that understands [`SettingSpec::flag`], set only when
+    /// the DCM changelog names it.
+    pub min_dcm_version: Option<&'static str>,
+    /// Set when the key still works but should no longer be relied on.
+    pub deprecation: Option<&'static str>,
+    pub toggle: Option<ToggleSpec>,
+    /// Reads the resolved value, with paths already expanded.
+    pub resolve: fn(&ResolvedSettings) -> Result<Value, DcmError>,
+}
+
+pub const SETTINGS: &[SettingSpec] = &[
+    SettingSpec {
+        key: "executable_path",
+        camel_key: "executablePath",
+        kind: SettingKind::Path,
+        default: SettingDefault::Unset,
+        description: "Path to the `dcm` executable. Defaults to the one found on PATH.",
+        flag: CliFlag::None,
+        sent_to_server: false,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| path_value(Some(&settings.executable_path)),
+    },
+    SettingSpec {
+        key: "dart_sdk_path",
+        camel_key: "dartSdkPath",
+        kind: SettingKind::Path,
+        default: SettingDefault::Unset,
+        description: "Path to the Dart SDK passed to the server with `--sdk-path`.",
+        flag: CliFlag::Value("--sdk-path"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| path_value(settings.sdk_path.as_ref()),
+    },
+    SettingSpec {
+        key: "show_new_version",
+        camel_key: "showNewVersion",
+        kind: SettingKind::Bool,
+        default: SettingDefault::Bool(true),
+        description: "Notify when a new DCM version is available.",
+        flag: CliFlag::IfFalse("--no-show-new-version-update"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: Some(ToggleSpec {
+            name: "new-version",
+            title: "Version Notification",
+            enabled: "New version notifications enabled.",
+            disabled: "New version notifications disabled.",
+        }),
+        resolve: |settings| Ok(Value::Bool(settings.layered.user.show_new_version)),
+    },
+    SettingSpec {
+        key: "show_unused_code",
+        camel_key: "showUnusedCode",
+        kind: SettingKind::Bool,
+        default: SettingDefault::Bool(false),
+        description: "Report unused code issues.",
+        flag: CliFlag::IfTrue("--show-unused-code"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: Some(ToggleSpec {
+            name: "unused-code",
+            title: "Unused Code",
+            enabled: "Unused code issues will be reported.",
+            disabled: "Unused code issues suppressed.",
+        }),
+        resolve: |settings| Ok(Value::Bool(settings.layered.user.show_unused_code)),
+    },
+    SettingSpec {
+        key: "show_unused_files",
+        camel_key: "showUnusedFiles",
+        kind: SettingKind::Bool,
+        default: SettingDefault::Bool(false),
+        description: "Report unused file issues.",
+        flag: CliFlag::IfTrue("--show-unused-files"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: Some(ToggleSpec {
+            name: "unused-files",
+            title: "Unused Files",
+            enabled: "Unused file analysis enabled.",
+            disabled: "Unused file analysis disabled.",
+        }),
+        resolve: |settings| Ok(Value::Bool(settings.layered.user.show_unused_files)),
+    },
+    SettingSpec {
+        key: "disable_baseline",
+        camel_key: "disableBaseline",
+        kind: SettingKind::Bool,
+        default: SettingDefault::Bool(false),
+        description: "Show issues that are suppressed by the baseline.",
+        flag: CliFlag::IfTrue("--disable-baseline"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: Some(ToggleSpec {
+            name: "baseline",
+            title: "Baseline",
+            enabled: "Baseline filtering disabled.",
+            disabled: "Baseline filtering enabled.",
+        }),
+        resolve: |settings| Ok(Value::Bool(settings.layered.user.disable_baseline)),
+    },
+    SettingSpec {
+        key: "enable_old_formatter",
+        camel_key: "enableOldFormatter",
+        kind: SettingKind::Bool,
+        default: SettingDefault::Bool(false),
+        description: "Use the formatter from Dart SDKs older than 3.7.",
+        flag: CliFlag::IfTrue("--old-formatter"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: Some(ToggleSpec {
+            name: "old-formatter",
+            title: "Old Formatter",
+            enabled: "The pre-Dart 3.7 formatter will be used.",
+            disabled: "The current Dart formatter will be used.",
+        }),
+        resolve: |settings| Ok(Value::Bool(settings.layered.user.enable_old_formatter)),
+    },
+    SettingSpec {
+        key: "analyze_only_opened",
+        camel_key: "analyzeOnlyOpened",
+        kind: SettingKind::Bool,
+        default: SettingDefault::Bool(false),
+        description: "Analyze only files that are open in the editor.",
+        flag: CliFlag::IfTrue("--only-opened"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: Some(ToggleSpec {
+            name: "only-opened",
+            title: "Only Opened Files",
+            enabled: "Only opened files will be analyzed.",
+            disabled: "The whole project will be analyzed.",
+        }),
+        resolve: |settings| Ok(Value::Bool(settings.layered.user.analyze_only_opened)),
+    },
+    SettingSpec {
+        key: "excluded_folders",
+        camel_key: "excludedFolders",
+        kind: SettingKind::PathList,
+        default: SettingDefault::EmptyList,
+        description: "Folders excluded from analysis, relative to the worktree root.",
+        flag: CliFlag::Value("--excluded-folders"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| {
+            let mut folders = Vec::with_capacity(settings.excluded_folders.len());
+            for path in &settings.excluded_folders {
+                folders.push(Value::String(path_to_string(path)?));
+            }
+            Ok(Value::Array(folders))
+        },
+    },
+    SettingSpec {
+        key: "log_file_path",
+        camel_key: "logFilePath",
+        kind: SettingKind::Path,
+        default: SettingDefault::Unset,
+        description: "File that captures the server communication.",
+        flag: CliFlag::Value("--logs"),
+        sent_to_server: true,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| path_value(settings.log_file_path.as_ref()),
+    },
+    SettingSpec {
+        key: "log_directory",
+        camel_key: "logDirectory",
+        kind: SettingKind::Path,
+        default: SettingDefault::Unset,
+        description: "Folder for per-session server logs. Each server start writes a new timestamped file there instead of `log_file_path`.",
+        flag: CliFlag::None,
+        sent_to_server: false,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| path_value(settings.log_directory.as_ref()),
+    },
+    SettingSpec {
+        key: "log_max_files",
+        camel_key: "logMaxFiles",
+        kind: SettingKind::Count,
+        default: SettingDefault::Count(10),
+        description: "Most session logs kept in `log_directory`; the oldest are deleted first.",
+        flag: CliFlag::None,
+        sent_to_server: false,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| Ok(Value::from(settings.layered.user.log_max_files)),
+    },
+    SettingSpec {
+        key: "log_max_size_mb",
+        camel_key: "logMaxSizeMb",
+        kind: SettingKind::Count,
+        default: SettingDefault::Count(50),
+        description: "Most megabytes of session logs kept in `log_directory`; the oldest are deleted first.",
+        flag: CliFlag::None,
+        sent_to_server: false,
+        requires_restart: true,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| Ok(Value::from(settings.layered.user.log_max_size_mb)),
+    },
+    SettingSpec {
+        key: "debug_log",
+        camel_key: "debugLog",
+        kind: SettingKind::Bool,
+        default: SettingDefault::Bool(false),
+        description: "Record how the extension resolves settings, the executable and the SDK in `.zed/dcm-extension.log`.",
+        flag: CliFlag::None,
+        sent_to_server: false,
+        requires_restart: false,
+        min_dcm_version: None,
+        deprecation: None,
+        toggle: None,
+        resolve: |settings| Ok(Value::Bool(settings.layered.user.debug_log)),
+    },
+];
+
+fn path_value(path: Option<&PathBuf>) -> Result<Value, DcmError> {
+    match path {
+        Some(path) => Ok(Value::String(path_to_string(path)?)),
+        None => Ok(Value::Null),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found while validating a `dcm` settings block.
+#[derive(Clone, Debug)]
+pub struct SettingsDiagnostic {
+    pub severity: Severity,
+    pub file: String,
+    pub message: String,
+}
+
+impl SettingsDiagnostic {
+    pub fn render(&self) -> String {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        format!("{level}: {}: {}", self.file, self.message)
+    }
+}
+
+pub fn spec(key: &str) -> Option<&'static SettingSpec> {
+    SETTINGS.iter().find(|spec| spec.key == key)
+}
+
+pub fn is_known(key: &str) -> bool {
+    spec(key).is_some()
+}
+
+/// Validates a raw `dcm` block read from `file`, returning the usable entries.
+///
+/// camelCase aliases are renamed to their snake_case key. Unknown keys and
+/// values of the wrong type are dropped so they cannot reach deserialization.
+pub fn normalize(
+    block: Map<String, Value>,
+    file: &str,
+    diagnostics: &mut Vec<SettingsDiagnostic>,
+) -> Map<String, Value> {
+    let mut normalized = Map::new();
+    let mut report = |severity, message: String| {
+        diagnostics.push(SettingsDiagnostic {
+            severity,
+            file: file.to_string(),
+            message,
+        })
+    };
+
+    for (raw_key, value) in block {
+        let spec = match spec(&raw_key) {
+            Some(spec) => spec,
+            None => match SETTINGS.iter().find(|spec| spec.camel_key == raw_key) {
+                Some(spec) => {
+                    report(
+                        Severity::Warning,
+                        format!(
+                            "`{raw_key}` is a VS Code-style alias; use `{}` instead",
+                            spec.key
+                        ),
+                    );
+                    spec
+                }
+                None => {
+                    let keys = SETTINGS.iter().map(|spec| spec.key);
+                    let hint = closest_match(&raw_key, keys)
+                        .map(|key| format!(" Did you mean `{key}`?"))
+                        .unwrap_or_default();
+                    report(
+                        Severity::Warning,
+                        format!("unknown setting `{raw_key}` is ignored.{hint}"),
+                    );
+                    continue;
+                }
+            },
+        };
+
+        if !spec.kind.accepts(&value) {
+            report(
+                Severity::Error,
+                format!(
+                    "`{}` must be {}, found `{value}`",
+                    spec.key,
+                    spec.kind.describe()
+                ),
+            );
+            continue;
+        }
+
+        if let Some(note) = spec.deprecation {
+            report(
+                Severity::Warning,
+                format!("`{}` is deprecated: {note}", spec.key),
+            );
+        }
+
+        normalized.insert(spec.key.to_string(), value);
+    }
+
+    normalized
+}
+
+/// Builds a JSON Schema (draft 7) describing the `dcm` settings block.
+pub fn json_schema() -> Value {
+    let mut properties = Map::new();
+
+    for spec in SETTINGS {
+        let mut property = match spec.kind {
+            SettingKind::Bool => json!({ "type": "boolean" }),
+            SettingKind::Path => json!({ "type": ["string", "null"] }),
+            SettingKind::PathList => json!({ "type": "array", "items": { "type": "string" } }),
+            SettingKind::Count => json!({ "type": "integer", "minimum": 1 }),
+        };
+        property["description"] = Value::String(spec.description.to_string());
+        property["default"] = spec.default.to_value();
+        if let Some(note) = spec.deprecation {
+            property["deprecated"] = Value::Bool(true);
+            property["deprecationMessage"] = Value::String(note.to_string());
+        }
+        properties.insert(spec.key.to_string(), property.clone());
+
+        // `normalize` still accepts the camelCase spelling, so the schema must
+        // not flag it as unknown; editors show it as deprecated instead.
+        if spec.camel_key != spec.key {
+            property["description"] = Value::String(format!("Alias of `{}`.", spec.key));
+            if let Some(object) = property.as_object_mut() {
+                object.remove("default");
+            }
+            property["deprecated"] = Value::Bool(true);
+            property["deprecationMessage"] =
+                Value::String(format!("VS Code-style alias; use `{}` instead", spec.key));
+            properties.insert(spec.camel_key.to_string(), property);
+        }
+    }
+
+    properties.insert(
+        PROFILE_KEY.to_string(),
+        json!({
+            "type": ["string", "null"],
+            "description": "Name of the profile from `profiles` to apply on top of these settings.",
+            "default": null,
+        }),
+    );
+    properties.insert(
+        PROFILES_KEY.to_string(),
+        json!({
+            "type": "object",
+            "description": "Named sets of settings that can be switched with `/dcm profile <name>`.",
+            "additionalProperties": { "$ref": "#/definitions/profile" },
+        }),
+    );
+    let profile_properties: Map<String, Value> = properties
+        .iter()
+        .filter(|(key, _)| *key != PROFILE_KEY && *key != PROFILES_KEY)
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "DCM settings for Zed",
+        "description": "The `dcm` block of a Zed settings file.",
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+        "definitions": {
+            "profile": {
+                "type": "object",
+                "properties": profile_properties,
+                "additionalProperties": false,
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UserSettings;
+
+    const SCHEMA_FILE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/schema/dcm-settings.schema.json"
+    );
+
+    #[test]
+    fn table_defaults_match_user_settings() {
+        let defaults = serde_json::to_value(UserSettings::default()).unwrap();
+        let object = defaults.as_object().unwrap();
+        assert_eq!(object.len(), SETTINGS.len());
+        for spec in SETTINGS {
+            assert_eq!(
+                object.get(spec.key),
+                Some(&spec.default.to_value()),
+                "{}",
+                spec.key
+            );
+        }
+    }
+
+    #[test]
+    fn command_line_flags_require_a_restart() {
+        for spec in SETTINGS {
+            if !matches!(spec.flag, CliFlag::None) {
+                assert!(spec.requires_restart, "{}", spec.key);
+            }
+        }
+    }
+
+    #[test]
+    fn schema_accepts_every_key_normalize_accepts() {
+        let schema = json_schema();
+        let properties = schema["properties"].as_object().unwrap();
+        let profile = schema["definitions"]["profile"]["properties"]
+            .as_object()
+            .unwrap();
+        for spec in SETTINGS {
+            for key in [spec.key, spec.camel_key] {
+                assert!(properties.contains_key(key), "{key}");
+                assert!(profile.contains_key(key), "{key}");
+            }
+        }
+        assert_eq!(properties["showUnusedCode"]["deprecated"], true);
+        assert!(properties["showUnusedCode"].get("default").is_none());
+    }
+
+    /// Regenerate the committed schema with `UPDATE_SCHEMA=1 cargo test`.
+    #[test]
+    fn committed_schema_golden() {
+        let generated = serde_json::to_string_pretty(&json_schema()).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(SCHEMA_FILE, &generated).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(SCHEMA_FILE).unwrap(), generated);
+    }
+}
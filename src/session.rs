@@ -0,0 +1,312 @@
+//! State the extension keeps between Zed callbacks.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde_json::{Map, Value};
+
+use crate::config::{self, LayeredSettings, ResolvedSettings, UserSettings};
+use crate::debug_log;
+use crate::error::DcmError;
+use crate::host::Host;
+use crate::log_sessions::{self, Retention};
+use crate::schema;
+
+/// How a setting changed since the server started has reached it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChangeStatus {
+    /// Only takes effect after the server restarts.
+    NeedsRestart,
+    /// Already delivered through workspace configuration.
+    Applied,
+    /// Applies live, but Zed has not requested workspace configuration since.
+    AwaitingConfiguration,
+    /// Only read by the extension, which uses the new value right away.
+    ExtensionOnly,
+}
+
+#[derive(Default)]
+pub struct SessionState {
+    /// Settings in effect when `language_server_command` last ran, by worktree id.
+    launched: Mutex<HashMap<u64, Map<String, Value>>>,
+    /// Settings last returned from workspace configuration, by worktree id.
+    configured: Mutex<HashMap<u64, Map<String, Value>>>,
+    /// Resolved settings by worktree id, with the fingerprint they were built from.
+    resolved: Mutex<HashMap<u64, (u64, ResolvedSettings)>>,
+    /// Log file of the current server session, by worktree id, in per-session log mode.
+    session_logs: Mutex<HashMap<u64, PathBuf>>,
+    /// Root of the worktree seen most recently. Slash command completion is
+    /// not given a worktree, so path completions are relative to this one.
+    last_root: Mutex<Option<PathBuf>>,
+}
+
+impl SessionState {
+    pub fn remember_worktree(&self, host: &dyn Host) {
+        if let Ok(mut root) = self.last_root.lock() {
+            *root = Some(host.root_path());
+        }
+    }
+
+    pub fn last_root(&self) -> Option<PathBuf> {
+        self.last_root.lock().ok().and_then(|root| root.clone())
+    }
+
+    /// Returns the resolved settings for the worktree behind `host`, reusing the cached copy
+    /// while the settings files and shell environment are unchanged.
+    pub fn resolve(&self, host: &dyn Host) -> Result<ResolvedSettings, DcmError> {
+        let fingerprint = config::settings_fingerprint(host, &host.shell_env());
+        let id = host.id();
+        let cached = self.resolved.lock().ok().and_then(|cache| {
+            cache
+                .get(&id)
+                .filter(|(cached, _)| *cached == fingerprint)
+                .map(|(_, settings)| settings.clone())
+        });
+
+        let mut settings = match cached {
+            Some(settings) => settings,
+            None => {
+                let mut trace = Vec::new();
+                let resolved = ResolvedSettings::from_host(host, &mut trace);
+                record_resolution(host, &resolved, trace);
+                let settings = resolved?;
+                if let Ok(mut cache) = self.resolved.lock() {
+                    cache.insert(id, (fingerprint, settings.clone()));
+                }
+                settings
+            }
+        };
+        if settings.log_directory.is_some() {
+            settings.log_file_path = self.session_log(id);
+        }
+        Ok(settings)
+    }
+
+    /// In per-session log mode, picks a new log file for a server about to
+    /// start, prunes old ones, and points `settings` at it.
+    pub fn start_log_session(
+        &self,
+        host: &dyn Host,
+        mut settings: ResolvedSettings,
+    ) -> Result<ResolvedSettings, DcmError> {
+        let Some(dir) = &settings.log_directory else {
+            return Ok(settings);
+        };
+        let retention = Retention::new(
+            settings.layered.user.log_max_files,
+            settings.layered.user.log_max_size_mb,
+        );
+        let path = log_sessions::start(host, dir, retention)?;
+        if let Ok(mut logs) = self.session_logs.lock() {
+            logs.insert(host.id(), path.clone());
+        }
+        settings.log_file_path = Some(path);
+        Ok(settings)
+    }
+
+    /// The log file of the current server session, in per-session log mode.
+    pub fn session_log(&self, worktree_id: u64) -> Option<PathBuf> {
+        self.session_logs
+            .lock()
+            .ok()
+            .and_then(|logs| logs.get(&worktree_id).cloned())
+    }
+
+    pub fn record_launch(&self, worktree_id: u64, user: &UserSettings) {
+        record(&self.launched, worktree_id, user);
+    }
+
+    pub fn record_configuration(&self, worktree_id: u64, user: &UserSettings) {
+        record(&self.configured, worktree_id, user);
+    }
+
+    /// Lists settings that differ from the ones the running server started with.
+    ///
+    /// Returns `None` when the server has not started for this worktree.
+    pub fn changes_since_launch(
+        &self,
+        worktree_id: u64,
+        current: &LayeredSettings,
+    ) -> Option<Vec<(&'static str, ChangeStatus)>> {
+        let launched = self.launched.lock().ok()?.get(&worktree_id)?.clone();
+        let configured = self
+            .configured
+            .lock()
+            .ok()
+            .and_then(|configured| configured.get(&worktree_id).cloned())
+            .unwrap_or_default();
+
+        let changes = schema::SETTINGS
+            .iter()
+            .filter_map(|spec| {
+                let value = current.value(spec.key);
+                if launched.get(spec.key) == Some(&value) {
+                    return None;
+                }
+                let status = if spec.requires_restart {
+                    ChangeStatus::NeedsRestart
+                } else if !spec.sent_to_server {
+                    ChangeStatus::ExtensionOnly
+                } else if configured.get(spec.key) == Some(&value) {
+                    ChangeStatus::Applied
+                } else {
+                    ChangeStatus::AwaitingConfiguration
+                };
+                Some((spec.key, status))
+            })
+            .collect();
+        Some(changes)
+    }
+
+    /// Describes [`SessionState::changes_since_launch`] for slash command output.
+    pub fn restart_summary(&self, host: &dyn Host, current: &LayeredSettings) -> String {
+        let Some(changes) = self.changes_since_launch(host.id(), current) else {
+            return "The DCM server has not started for this worktree yet; changes apply when it starts."
+                .to_string();
+        };
+        if changes.is_empty() {
+            return "The running DCM server is up to date.".to_string();
+        }
+
+        let keys = |wanted: ChangeStatus| {
+            changes
+                .iter()
+                .filter(|(_, status)| *status == wanted)
+                .map(|(key, _)| format!("`{key}`"))
+                .collect::<Vec<_>>()
+        };
+        let mut parts = Vec::new();
+        let pending = keys(ChangeStatus::NeedsRestart);
+        if !pending.is_empty() {
+            parts.push(format!(
+                "Restart the DCM server to apply {}.",
+                pending.join(", ")
+            ));
+        }
+        let applied = keys(ChangeStatus::Applied);
+        if !applied.is_empty() {
+            parts.push(format!(
+                "Applied through workspace configuration: {}.",
+                applied.join(", ")
+            ));
+        }
+        let awaiting = keys(ChangeStatus::AwaitingConfiguration);
+        if !awaiting.is_empty() {
+            parts.push(format!(
+                "Sent with the next workspace configuration update: {}.",
+                awaiting.join(", ")
+            ));
+        }
+        let extension = keys(ChangeStatus::ExtensionOnly);
+        if !extension.is_empty() {
+            parts.push(format!(
+                "Applied by the extension: {}.",
+                extension.join(", ")
+            ));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Writes the decisions behind a fresh resolution to the debug log, if enabled.
+///
+/// When resolution fails, the setting is read from the layered settings alone.
+fn record_resolution(
+    host: &dyn Host,
+    resolved: &Result<ResolvedSettings, DcmError>,
+    mut trace: Vec<String>,
+) {
+    let enabled = match resolved {
+        Ok(settings) => settings.layered.user.debug_log,
+        Err(_) => LayeredSettings::load(host).is_ok_and(|layered| layered.user.debug_log),
+    };
+    if !enabled {
+        return;
+    }
+    let event = match resolved {
+        Ok(_) => "resolved settings",
+        Err(err) => {
+            trace.push(debug_log::error_line(err));
+            "failed to resolve settings"
+        }
+    };
+    debug_log::record(host, &host.root_path(), event, &trace);
+}
+
+fn record(
+    snapshots: &Mutex<HashMap<u64, Map<String, Value>>>,
+    worktree_id: u64,
+    user: &UserSettings,
+) {
+    let Ok(Value::Object(values)) = serde_json::to_value(user) else {
+        return;
+    };
+    if let Ok(mut snapshots) = snapshots.lock() {
+        snapshots.insert(worktree_id, values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PROJECT_SETTINGS_FILE;
+    use crate::debug_log::DEBUG_LOG_FILE;
+    use crate::host::fake::FakeHost;
+
+    #[test]
+    fn restart_summary_tells_server_and_extension_settings_apart() {
+        let host = FakeHost::new().with_binary("dcm", "/usr/bin/dcm");
+        let session = SessionState::default();
+        let summary =
+            |host: &FakeHost| session.restart_summary(host, &LayeredSettings::load(host).unwrap());
+        assert!(summary(&host).contains("has not started"));
+
+        session.record_launch(host.id(), &UserSettings::default());
+        assert_eq!(summary(&host), "The running DCM server is up to date.");
+
+        let host = host.with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{ "dcm": { "debug_log": true, "show_unused_code": true } }"#,
+        );
+        assert_eq!(
+            summary(&host),
+            "Restart the DCM server to apply `show_unused_code`. Applied by the extension: `debug_log`."
+        );
+    }
+
+    #[test]
+    fn failed_resolution_is_traced_when_the_project_enables_debug_log() {
+        let host = FakeHost::new().with_file(
+            PROJECT_SETTINGS_FILE,
+            r#"{ "dcm": { "debug_log": true, "executable_path": "/missing/dcm" } }"#,
+        );
+
+        let err = SessionState::default().resolve(&host).unwrap_err();
+        let log = host.file(DEBUG_LOG_FILE).unwrap();
+        assert!(log.contains("] failed to resolve settings\n"));
+        assert!(log.contains(&format!("  error {}: ", err.code())));
+        assert!(log.contains("/missing/dcm"));
+    }
+
+    #[test]
+    fn resolution_is_not_traced_without_debug_log() {
+        let host = FakeHost::new().with_binary("dcm", "/usr/bin/dcm");
+
+        SessionState::default().resolve(&host).unwrap();
+        assert!(host.file(DEBUG_LOG_FILE).is_none());
+    }
+
+    #[test]
+    fn resolution_is_traced_once_while_settings_are_unchanged() {
+        let host = FakeHost::new()
+            .with_binary("dcm", "/usr/bin/dcm")
+            .with_file(PROJECT_SETTINGS_FILE, r#"{ "dcm": { "debug_log": true } }"#);
+        let session = SessionState::default();
+
+        session.resolve(&host).unwrap();
+        session.resolve(&host).unwrap();
+        let log = host.file(DEBUG_LOG_FILE).unwrap();
+        assert_eq!(log.matches("] resolved settings").count(), 1);
+    }
+}
@@ -0,0 +1,408 @@
+//! Approval of executables and file locations configured by files inside the
+//! worktree.
+//!
+//! A cloned repository can point `executable_path` or `dart_sdk_path` at any
+//! binary, which Zed would run as soon as a Dart file opens. Values that come
+//! from the team file, the project file or a profile, and that point outside
+//! the directories on the shell PATH (or, for log locations, outside the
+//! worktree), must be approved with `/dcm trust approve` first. Approved
+//! executables and SDKs are remembered by size and modification time, so
+//! replacing the binary asks for approval again.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, ResolvedSettings, SettingSource};
+use crate::error::DcmError;
+use crate::host::FileSystem;
+use crate::utils::{
+    canonicalize_if_possible, env_value, format_age, path_to_string, unix_timestamp,
+};
+
+/// File next to the user-level Zed settings that records approvals.
+const TRUST_FILE_NAME: &str = "dcm-trusted-paths.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TrustStore {
+    approvals: Vec<Approval>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Approval {
+    worktree: String,
+    key: String,
+    path: String,
+    /// Seconds since the Unix epoch.
+    approved_at: u64,
+    /// The approved program as it was then; log locations have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<Fingerprint>,
+}
+
+/// Size and modification time of the program a path runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    len: u64,
+    modified: u64,
+}
+
+/// Why a configured path may or may not be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustStatus {
+    /// Set outside the worktree, by the user or the environment.
+    UserConfigured,
+    /// Inside a directory on PATH, or the worktree for log locations.
+    KnownLocation,
+    /// Approved at the given Unix time.
+    Approved(u64),
+    /// Approved at the given Unix time, but the program has changed since.
+    Changed(u64),
+    /// Needs approval before the server may start.
+    Pending,
+}
+
+impl TrustStatus {
+    /// Whether the path must be approved before the server may start.
+    pub fn needs_approval(&self) -> bool {
+        matches!(self, TrustStatus::Changed(_) | TrustStatus::Pending)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrustedPath {
+    pub key: &'static str,
+    pub path: PathBuf,
+    pub source: SettingSource,
+    pub status: TrustStatus,
+}
+
+impl TrustedPath {
+    pub fn render(&self) -> String {
+        let status = match self.status {
+            TrustStatus::UserConfigured => "trusted, not set by the worktree".to_string(),
+            TrustStatus::KnownLocation => "trusted, known location".to_string(),
+            TrustStatus::Approved(at) => format!("approved {}", format_age(at)),
+            TrustStatus::Changed(at) => {
+                format!("CHANGED since it was approved {}", format_age(at))
+            }
+            TrustStatus::Pending => "NOT APPROVED".to_string(),
+        };
+        format!(
+            "{}: {} ({}, {status})",
+            self.key,
+            self.path.display(),
+            self.source.label()
+        )
+    }
+}
+
+/// Lists the configured executable, SDK and log file paths with their trust status.
+pub fn review(fs: &dyn FileSystem, settings: &ResolvedSettings) -> Vec<TrustedPath> {
+    let approvals = load_store(fs, settings).approvals;
+    let worktree = settings.root_path.to_string_lossy();
+
+    let mut configured = Vec::new();
+    if settings.layered.user.executable_path.is_some() {
+        configured.push(("executable_path", settings.executable_path.clone()));
+    }
+    if let Some(path) = &settings.sdk_path {
+        configured.push(("dart_sdk_path", path.clone()));
+    }
+    // Session files inside `log_directory` are covered by approving the directory.
+    match (&settings.log_directory, &settings.log_file_path) {
+        (Some(dir), _) => configured.push(("log_directory", dir.clone())),
+        (None, Some(path)) => configured.push(("log_file_path", path.clone())),
+        (None, None) => {}
+    }
+
+    configured
+        .into_iter()
+        .map(|(key, path)| {
+            let path = canonicalize_if_possible(fs, path);
+            let source = settings.layered.source_of(key);
+            let status = if !from_worktree(source) {
+                TrustStatus::UserConfigured
+            } else if is_known_location(fs, key, &path, settings) {
+                TrustStatus::KnownLocation
+            } else {
+                approvals
+                    .iter()
+                    .find(|approval| {
+                        approval.worktree == worktree
+                            && approval.key == key
+                            && Path::new(&approval.path) == path
+                    })
+                    .map(|approval| {
+                        if approval.fingerprint == fingerprint(fs, key, &path) {
+                            TrustStatus::Approved(approval.approved_at)
+                        } else {
+                            TrustStatus::Changed(approval.approved_at)
+                        }
+                    })
+                    .unwrap_or(TrustStatus::Pending)
+            };
+            TrustedPath {
+                key,
+                path,
+                source,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// Fails with instructions when any worktree-configured path still needs approval.
+pub fn ensure_trusted(fs: &dyn FileSystem, settings: &ResolvedSettings) -> Result<(), DcmError> {
+    let pending: Vec<String> = review(fs, settings)
+        .iter()
+        .filter(|entry| entry.status.needs_approval())
+        .map(TrustedPath::render)
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    Err(DcmError::Untrusted(pending))
+}
+
+/// Records approval of every pending path and returns the ones approved.
+pub fn approve(
+    fs: &dyn FileSystem,
+    settings: &ResolvedSettings,
+) -> Result<Vec<TrustedPath>, DcmError> {
+    let pending: Vec<TrustedPath> = review(fs, settings)
+        .into_iter()
+        .filter(|entry| entry.status.needs_approval())
+        .collect();
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+
+    let worktree = path_to_string(&settings.root_path)?;
+    let mut store = load_store(fs, settings);
+    for entry in &pending {
+        store
+            .approvals
+            .retain(|approval| approval.worktree != worktree || approval.key != entry.key);
+        store.approvals.push(Approval {
+            worktree: worktree.clone(),
+            key: entry.key.to_string(),
+            path: path_to_string(&entry.path)?,
+            approved_at: unix_timestamp(),
+            fingerprint: fingerprint(fs, entry.key, &entry.path),
+        });
+    }
+    save_store(fs, settings, &store)?;
+    Ok(pending)
+}
+
+/// Forgets every approval recorded for this worktree, returning how many there were.
+pub fn revoke(fs: &dyn FileSystem, settings: &ResolvedSettings) -> Result<usize, DcmError> {
+    let worktree = path_to_string(&settings.root_path)?;
+    let mut store = load_store(fs, settings);
+    let before = store.approvals.len();
+    store
+        .approvals
+        .retain(|approval| approval.worktree != worktree);
+    let removed = before - store.approvals.len();
+    if removed > 0 {
+        save_store(fs, settings, &store)?;
+    }
+    Ok(removed)
+}
+
+fn from_worktree(source: SettingSource) -> bool {
+    matches!(
+        source,
+        SettingSource::Team | SettingSource::Project | SettingSource::Profile
+    )
+}
+
+fn is_known_location(
+    fs: &dyn FileSystem,
+    key: &str,
+    path: &Path,
+    settings: &ResolvedSettings,
+) -> bool {
+    if key == "log_file_path" || key == "log_directory" {
+        return path.starts_with(&settings.root_path);
+    }
+
+    path_dirs(fs, settings).iter().any(|dir| match key {
+        "executable_path" => path.parent() == Some(dir.as_path()),
+        // Covers both `<sdk>/bin` on PATH and SDKs bundled below a PATH entry,
+        // such as Flutter's `bin/cache/dart-sdk`.
+        _ => path.starts_with(dir) || path.join("bin") == *dir,
+    })
+}
+
+/// Identifies the program behind an executable or SDK path, if it exists.
+fn fingerprint(fs: &dyn FileSystem, key: &str, path: &Path) -> Option<Fingerprint> {
+    let program = match key {
+        "executable_path" => path.to_path_buf(),
+        "dart_sdk_path" => ["dart", "dart.exe"]
+            .iter()
+            .map(|name| path.join("bin").join(name))
+            .find(|program| fs.is_file(program))?,
+        _ => return None,
+    };
+    fs.metadata(&program)
+        .filter(|metadata| !metadata.is_dir)
+        .map(|metadata| Fingerprint {
+            len: metadata.len,
+            modified: metadata.modified,
+        })
+}
+
+/// Absolute PATH entries from the shell environment, excluding the worktree
+/// and the folders that contain it, such as `$HOME` or `/`.
+fn path_dirs(fs: &dyn FileSystem, settings: &ResolvedSettings) -> Vec<PathBuf> {
+    let Some(path) = env_value(&settings.env, "PATH") else {
+        return Vec::new();
+    };
+    let separator = if path.contains(';') { ';' } else { ':' };
+    path.split(separator)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| canonicalize_if_possible(fs, dir))
+        .filter(|dir| !dir.starts_with(&settings.root_path) && !settings.root_path.starts_with(dir))
+        .collect()
+}
+
+fn store_path(settings: &ResolvedSettings) -> Option<PathBuf> {
+    config::user_settings_path(&settings.env)
+        .and_then(|path| path.parent().map(|dir| dir.join(TRUST_FILE_NAME)))
+}
+
+fn load_store(fs: &dyn FileSystem, settings: &ResolvedSettings) -> TrustStore {
+    store_path(settings)
+        .and_then(|path| fs.read_file(&path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_store(
+    fs: &dyn FileSystem,
+    settings: &ResolvedSettings,
+    store: &TrustStore,
+) -> Result<(), DcmError> {
+    let Some(path) = store_path(settings) else {
+        return Err(DcmError::filesystem(
+            "locate",
+            Path::new("the Zed config directory"),
+            "none of XDG_CONFIG_HOME, APPDATA or HOME is set",
+        ));
+    };
+    let serialized = serde_json::to_string_pretty(store)
+        .map_err(|err| DcmError::settings(format!("Failed to serialize approvals: {err}")))?;
+    fs.write_file(&path, &serialized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PROJECT_SETTINGS_FILE;
+    use crate::host::fake::FakeHost;
+
+    fn resolve(host: &FakeHost) -> ResolvedSettings {
+        ResolvedSettings::from_host(host, &mut Vec::new()).unwrap()
+    }
+
+    fn status(host: &FakeHost, key: &str) -> TrustStatus {
+        review(host, &resolve(host))
+            .into_iter()
+            .find(|entry| entry.key == key)
+            .unwrap()
+            .status
+    }
+
+    fn project(dcm: &str) -> FakeHost {
+        FakeHost::new().with_file(PROJECT_SETTINGS_FILE, &format!(r#"{{ "dcm": {dcm} }}"#))
+    }
+
+    #[test]
+    fn executables_on_path_are_known() {
+        let host =
+            project(r#"{ "executable_path": "/usr/bin/dcm" }"#).with_file("/usr/bin/dcm", "");
+        assert_eq!(status(&host, "executable_path"), TrustStatus::KnownLocation);
+    }
+
+    #[test]
+    fn path_entries_containing_the_worktree_are_not_known_locations() {
+        let host = project(r#"{ "executable_path": "/work/dcm", "dart_sdk_path": "/work/sdk" }"#)
+            .with_file("/work/dcm", "")
+            .with_file("/work/sdk/bin/dart", "")
+            .with_env("PATH", "/work:/:/usr/bin");
+
+        assert_eq!(status(&host, "executable_path"), TrustStatus::Pending);
+        assert_eq!(status(&host, "dart_sdk_path"), TrustStatus::Pending);
+    }
+
+    #[test]
+    fn log_locations_inside_the_worktree_are_known() {
+        let host =
+            project(r#"{ "log_directory": ".zed/dcm-logs" }"#).with_binary("dcm", "/usr/bin/dcm");
+        assert_eq!(status(&host, "log_directory"), TrustStatus::KnownLocation);
+
+        let host =
+            project(r#"{ "log_file_path": "/tmp/dcm.log" }"#).with_binary("dcm", "/usr/bin/dcm");
+        assert_eq!(status(&host, "log_file_path"), TrustStatus::Pending);
+    }
+
+    #[test]
+    fn user_settings_are_trusted() {
+        let host = FakeHost::new().with_file("/opt/dcm", "").with_file(
+            "/home/dev/.config/zed/settings.json",
+            r#"{ "dcm": { "executable_path": "/opt/dcm" } }"#,
+        );
+        assert_eq!(
+            status(&host, "executable_path"),
+            TrustStatus::UserConfigured
+        );
+        assert!(ensure_trusted(&host, &resolve(&host)).is_ok());
+    }
+
+    #[test]
+    fn approval_is_revoked_when_the_executable_changes() {
+        let host = project(r#"{ "executable_path": "/opt/dcm" }"#).with_file("/opt/dcm", "v1");
+        let settings = resolve(&host);
+        assert_eq!(
+            ensure_trusted(&host, &settings).unwrap_err().code(),
+            "DCM202"
+        );
+
+        assert_eq!(approve(&host, &settings).unwrap().len(), 1);
+        assert!(matches!(
+            status(&host, "executable_path"),
+            TrustStatus::Approved(_)
+        ));
+
+        host.write_file(Path::new("/opt/dcm"), "v2").unwrap();
+        assert!(matches!(
+            status(&host, "executable_path"),
+            TrustStatus::Changed(_)
+        ));
+        assert!(ensure_trusted(&host, &settings).is_err());
+
+        approve(&host, &settings).unwrap();
+        assert!(ensure_trusted(&host, &settings).is_ok());
+        assert_eq!(revoke(&host, &settings).unwrap(), 1);
+        assert_eq!(status(&host, "executable_path"), TrustStatus::Pending);
+    }
+
+    #[test]
+    fn sdk_approval_follows_the_dart_binary() {
+        let host = project(r#"{ "dart_sdk_path": "/opt/sdk" }"#)
+            .with_binary("dcm", "/usr/bin/dcm")
+            .with_file("/opt/sdk/bin/dart", "v1");
+        approve(&host, &resolve(&host)).unwrap();
+
+        host.write_file(Path::new("/opt/sdk/bin/dart"), "v2")
+            .unwrap();
+        assert!(matches!(
+            status(&host, "dart_sdk_path"),
+            TrustStatus::Changed(_)
+        ));
+    }
+}
@@ -1,15 +1,18 @@
 use std::collections::HashMap;
-use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use shellexpand::full_with_context_no_errors;
 use zed_extension_api::EnvVars;
 
+use crate::error::DcmError;
+use crate::host::FileSystem;
+
 /// Resolves a potentially relative or shell-expanded path into an absolute [`PathBuf`].
-pub fn resolve_path(raw: &str, env: &EnvVars, worktree_root: &Path) -> Result<PathBuf, String> {
+pub fn resolve_path(raw: &str, env: &EnvVars, worktree_root: &Path) -> Result<PathBuf, DcmError> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
-        return Err("path is empty".to_string());
+        return Err(DcmError::settings("path is empty"));
     }
 
     let env_map: HashMap<String, String> = env.iter().cloned().collect();
@@ -30,7 +33,7 @@ pub fn resolve_path(raw: &str, env: &EnvVars, worktree_root: &Path) -> Result<Pa
 }
 
 /// Converts a [`Path`] into a UTF-8 [`String`], returning an error if conversion fails.
-pub fn path_to_string(path: &Path) -> Result<String, String> {
+pub fn path_to_string(path: &Path) -> Result<String, DcmError> {
     Ok(path
         .to_str()
         .map(|s| s.to_owned())
@@ -38,6 +41,219 @@ pub fn path_to_string(path: &Path) -> Result<String, String> {
 }
 
 /// Attempts to canonicalize a path, returning the original path if canonicalization fails.
-pub fn canonicalize_if_possible(path: PathBuf) -> PathBuf {
-    fs::canonicalize(&path).unwrap_or(path)
+pub fn canonicalize_if_possible(fs: &dyn FileSystem, path: PathBuf) -> PathBuf {
+    fs.canonicalize(&path).unwrap_or(path)
+}
+
+/// Looks up a variable in the worktree shell environment.
+pub fn env_value(env: &EnvVars, key: &str) -> Option<String> {
+    env.iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.clone())
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Describes how long ago `timestamp` (seconds since the Unix epoch) was, e.g. `3h ago`.
+pub fn format_age(timestamp: u64) -> String {
+    let elapsed = unix_timestamp().saturating_sub(timestamp);
+    match elapsed {
+        0..=59 => format!("{elapsed}s ago"),
+        60..=3599 => format!("{}m ago", elapsed / 60),
+        3600..=86399 => format!("{}h ago", elapsed / 3600),
+        _ => format!("{}d ago", elapsed / 86400),
+    }
+}
+
+/// Returns the candidate closest to `input` by edit distance, if any is close enough.
+pub fn closest_match<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let threshold = (input.len() / 3).max(2);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b_chars.len() + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b_chars.len()]
+}
+
+const SECRET_MARKERS: &[&str] = &[
+    "KEY",
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "LICENSE",
+    "CREDENTIAL",
+];
+
+/// Returns `true` when a variable or flag name suggests its value is a secret.
+pub fn is_secret_name(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase().replace('-', "_");
+    SECRET_MARKERS.iter().any(|marker| upper.contains(marker))
+}
+
+/// Masks the value of `--flag=value` arguments whose flag name looks secret.
+pub fn redact_arg(arg: &str) -> String {
+    match arg.split_once('=') {
+        Some((flag, _)) if is_secret_name(flag) => format!("{flag}=<redacted>"),
+        _ => arg.to_string(),
+    }
+}
+
+/// Replaces home directories with `~` and masks secret-looking values in free text.
+///
+/// Values are masked when they follow a secret name (`--license-key=...`,
+/// `TOKEN: ...`, `"api_key": "..."`), and long opaque tokens are masked too.
+pub fn scrub_text(text: &str, env: &EnvVars) -> String {
+    let mut scrubbed = text.to_string();
+    for name in ["HOME", "USERPROFILE"] {
+        if let Some(home) = env_value(env, name).filter(|home| home.len() > 1) {
+            scrubbed = replace_home(&scrubbed, &home);
+        }
+    }
+
+    let mut lines: Vec<String> = scrubbed.lines().map(scrub_line).collect();
+    if scrubbed.ends_with('\n') {
+        lines.push(String::new());
+    }
+    lines.join("\n")
+}
+
+/// Replaces `home` with `~` where it is a whole path component, so `/home/al`
+/// leaves `/home/alice` alone.
+fn replace_home(text: &str, home: &str) -> String {
+    let home = home.trim_end_matches(['/', '\\']);
+    if home.is_empty() {
+        return text.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(position) = rest.find(home) {
+        let after = &rest[position + home.len()..];
+        let ends_component = after
+            .chars()
+            .next()
+            .is_none_or(|next| !(next.is_alphanumeric() || matches!(next, '-' | '_' | '.')));
+        result.push_str(&rest[..position]);
+        result.push_str(if ends_component { "~" } else { home });
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+fn scrub_line(line: &str) -> String {
+    let mut redact_next = false;
+    line.split(' ')
+        .map(|word| {
+            if std::mem::take(&mut redact_next) && !word.is_empty() {
+                return "<redacted>".to_string();
+            }
+            if let Some(position) = word.find(['=', ':']) {
+                let (name, rest) = word.split_at(position);
+                let name = name.trim_matches(|c: char| c == '"' || c == '-' || c == '\'');
+                if is_secret_name(name) {
+                    if rest.len() > 1 {
+                        return format!("{}{}<redacted>", &word[..position], &rest[..1]);
+                    }
+                    redact_next = true;
+                }
+            }
+            if looks_like_token(word) {
+                return "<redacted>".to_string();
+            }
+            word.to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Long runs of letters, digits, `-` and `_` mixing letters and digits, such as license keys.
+fn looks_like_token(word: &str) -> bool {
+    let word = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    word.len() >= 32
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && word.chars().any(|c| c.is_ascii_digit())
+        && word.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+/// Returns up to the last `lines` lines of `path`, reading at most `max_bytes` from its end.
+pub fn tail_lines(
+    fs: &dyn FileSystem,
+    path: &Path,
+    lines: usize,
+    max_bytes: u64,
+) -> Option<String> {
+    let (text, _) = read_tail(fs, path, max_bytes)?;
+    let all: Vec<&str> = text.lines().collect();
+    let skip = all.len().saturating_sub(lines);
+    Some(all[skip..].join("\n"))
+}
+
+/// Reads at most the last `max_bytes` of `path`.
+///
+/// When the file is longer, the partial first line is dropped and `true` is
+/// returned alongside the text.
+pub fn read_tail(fs: &dyn FileSystem, path: &Path, max_bytes: u64) -> Option<(String, bool)> {
+    let length = fs.metadata(path).filter(|metadata| !metadata.is_dir)?.len;
+    let start = length.saturating_sub(max_bytes);
+    let bytes = fs.read_from(path, start).ok()?;
+
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    if start == 0 {
+        return Some((text, false));
+    }
+    let text = match text.split_once('\n') {
+        Some((_, rest)) => rest.to_string(),
+        None => String::new(),
+    };
+    Some((text, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrub_text_replaces_whole_home_directories_only() {
+        let env = vec![("HOME".to_string(), "/home/al".to_string())];
+        assert_eq!(
+            scrub_text("/home/al/app /home/alice/app /home/al \"/home/al\"", &env),
+            "~/app /home/alice/app ~ \"~\""
+        );
+    }
+
+    #[test]
+    fn scrub_text_masks_secrets() {
+        assert_eq!(
+            scrub_text("dcm --license-key=ABC token: xyz", &Vec::new()),
+            "dcm --license-key=<redacted> token: <redacted>"
+        );
+    }
 }
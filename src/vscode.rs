@@ -0,0 +1,154 @@
+//! Translation between the Zed `dcm` block and the `dcm.*` keys used by the
+//! DCM extension for VS Code.
+
+use serde_json::{Map, Value};
+
+use crate::schema::{self, SettingSpec};
+
+/// VS Code workspace settings, relative to the worktree root.
+pub const VSCODE_SETTINGS_FILE: &str = ".vscode/settings.json";
+
+const VSCODE_PREFIX: &str = "dcm.";
+
+/// The result of translating settings from one editor's format to the other.
+#[derive(Debug, Default)]
+pub struct Conversion {
+    /// Translated entries, keyed as the target editor expects them.
+    pub values: Map<String, Value>,
+    /// Source keys that were not translated, with the reason.
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Maps the `dcm.*` keys of a VS Code settings object onto Zed `dcm` keys.
+pub fn import(vscode: &Map<String, Value>) -> Conversion {
+    let mut conversion = Conversion::default();
+    for (key, value) in vscode {
+        let Some(name) = key.strip_prefix(VSCODE_PREFIX) else {
+            continue;
+        };
+        match find_spec(name) {
+            Some(spec) if spec.kind.accepts(value) => {
+                conversion
+                    .values
+                    .insert(spec.key.to_string(), value.clone());
+            }
+            Some(spec) => conversion.skipped.push((
+                key.clone(),
+                format!("expected {}, found {value}", spec.kind.describe()),
+            )),
+            None => conversion
+                .skipped
+                .push((key.clone(), "no Zed equivalent".to_string())),
+        }
+    }
+    conversion
+}
+
+/// Maps a Zed `dcm` block onto VS Code `dcm.*` keys.
+pub fn export(dcm: &Map<String, Value>) -> Conversion {
+    let mut conversion = Conversion::default();
+    for (key, value) in dcm {
+        match find_spec(key) {
+            Some(spec) => {
+                conversion
+                    .values
+                    .insert(format!("{VSCODE_PREFIX}{}", spec.camel_key), value.clone());
+            }
+            None => conversion
+                .skipped
+                .push((format!("dcm.{key}"), "no VS Code equivalent".to_string())),
+        }
+    }
+    conversion
+}
+
+fn find_spec(key: &str) -> Option<&'static SettingSpec> {
+    schema::SETTINGS
+        .iter()
+        .find(|spec| spec.camel_key == key || spec.key == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn import_maps_camel_case_keys() {
+        let conversion = import(&object(json!({
+            "dcm.showUnusedCode": true,
+            "dcm.excludedFolders": ["build"],
+            "dcm.dart_sdk_path": "/opt/sdk",
+            "editor.formatOnSave": true,
+        })));
+
+        assert_eq!(
+            Value::Object(conversion.values),
+            json!({
+                "show_unused_code": true,
+                "excluded_folders": ["build"],
+                "dart_sdk_path": "/opt/sdk",
+            })
+        );
+        assert!(conversion.skipped.is_empty());
+    }
+
+    #[test]
+    fn import_skips_unknown_keys_and_wrong_types() {
+        let conversion = import(&object(json!({
+            "dcm.telemetry": false,
+            "dcm.showUnusedFiles": "yes",
+        })));
+
+        assert!(conversion.values.is_empty());
+        assert_eq!(
+            conversion.skipped,
+            [
+                (
+                    "dcm.showUnusedFiles".to_string(),
+                    "expected a boolean, found \"yes\"".to_string()
+                ),
+                ("dcm.telemetry".to_string(), "no Zed equivalent".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let dcm = object(json!({
+            "show_unused_code": true,
+            "log_max_files": 5,
+            "excluded_folders": ["build", "lib/gen"],
+            "profile": "ci",
+        }));
+
+        let exported = export(&dcm);
+        assert_eq!(
+            Value::Object(exported.values.clone()),
+            json!({
+                "dcm.showUnusedCode": true,
+                "dcm.logMaxFiles": 5,
+                "dcm.excludedFolders": ["build", "lib/gen"],
+            })
+        );
+        assert_eq!(
+            exported.skipped,
+            [(
+                "dcm.profile".to_string(),
+                "no VS Code equivalent".to_string()
+            )]
+        );
+
+        let mut expected = dcm;
+        expected.remove("profile");
+        assert_eq!(import(&exported.values).values, expected);
+    }
+}